@@ -0,0 +1,1284 @@
+//! argon2 hashing, verification and rehash checks.
+
+use argon2::{
+    password_hash::{Output, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2, AssociatedData, KeyId, Params, ParamsBuilder, Version,
+};
+use base64::{
+    engine::general_purpose::{STANDARD as BASE64, STANDARD_NO_PAD as BASE64_NO_PAD},
+    Engine,
+};
+use worker::{Date, Delay, Env, Request};
+
+use crate::body::{parse_body, parse_body_with_raw, read_text_body};
+use crate::config::{Argon2Limits, Argon2Secret, Config, Defaults};
+use crate::encoding::{Encoding, decode};
+use crate::error::{push_field_error, Error, FieldError};
+use crate::idempotency::Idempotency;
+use crate::metrics;
+use crate::password::{
+    check_password_breached, check_password_control_chars, check_password_length,
+    check_password_present, check_password_strength, check_pre_hashed, decode_password, split_pepper_version,
+    tag_pepper_version, with_context,
+};
+use crate::random_handler::generate_salt;
+use crate::server_timing::ServerTiming;
+use crate::types::{
+    BatchHashRequest, BatchHashResponse, BatchHashResult, HashRequest, HashResponse, MAX_BATCH_SIZE,
+    NeedsRehashRequest, NeedsRehashResponse, VerifyResponse,
+};
+use crate::verify_handler::{check_algorithm, Algorithm};
+
+// ### Types
+#[derive(serde::Deserialize, Clone, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct Argon2HashOptions {
+    /// One of `owasp_min`, `owasp_moderate` or `owasp_sensitive`; explicit
+    /// costs given alongside it must match the preset's.
+    pub preset: Option<String>,
+    pub time_cost: Option<u32>,
+    pub memory_cost: Option<u32>,
+    pub parallelism: Option<u32>,
+    #[serde(default)]
+    pub variant: Argon2Variant,
+    /// Base64, at most 8 bytes once decoded.
+    pub keyid: Option<String>,
+    /// Base64 associated data, at most 32 bytes once decoded.
+    pub data: Option<String>,
+    /// Bytes of hash output; the PHC string format limits it to 10..=64.
+    pub output_length: Option<usize>,
+    /// Fixed salt for reproducible hashes, only honoured with `ALLOW_CUSTOM_SALT`.
+    pub salt: Option<String>,
+    /// `19` (0x13, the default) or `16` (0x10) for hashes legacy verifiers accept.
+    pub version: Option<u32>,
+    /// `phc` (the default) or `components`; only `/argon2/hash` honours it.
+    #[serde(default)]
+    pub output_format: Argon2OutputFormat,
+}
+
+#[derive(serde::Deserialize, Default, Clone, Copy, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Argon2OutputFormat {
+    #[default]
+    Phc,
+    Components,
+}
+
+#[derive(serde::Deserialize, Default, Clone, Copy, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Argon2Variant {
+    #[default]
+    Argon2id,
+    Argon2i,
+    Argon2d,
+}
+
+impl From<Argon2Variant> for argon2::Algorithm {
+    fn from(variant: Argon2Variant) -> Self {
+        match variant {
+            Argon2Variant::Argon2id => argon2::Algorithm::Argon2id,
+            Argon2Variant::Argon2i => argon2::Algorithm::Argon2i,
+            Argon2Variant::Argon2d => argon2::Algorithm::Argon2d,
+        }
+    }
+}
+
+impl Argon2HashOptions {
+    // Every problem with the fields at once, rather than the first, so a form
+    // can mark them all. Conflicts between fields are left to `validate`.
+    pub fn field_errors(&self, limits: &Argon2Limits) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if let Some(preset) = self.preset.as_deref() {
+            push_field_error(&mut errors, "preset", argon2_preset(preset));
+        }
+
+        let costs = [
+            ("memory_cost", self.memory_cost, Params::MIN_M_COST, limits.max_memory_kib),
+            ("time_cost", self.time_cost, Params::MIN_T_COST, limits.max_time_cost),
+            ("parallelism", self.parallelism, Params::MIN_P_COST, limits.max_parallelism),
+        ];
+        for (field, cost, min, max) in costs {
+            let message = match cost {
+                None if self.preset.is_none() => format!("{field} is required without a preset"),
+                Some(cost) if cost < min => format!("{field} must be at least {min}"),
+                Some(cost) if cost > max => format!("{field} must be at most {max}"),
+                _ => continue,
+            };
+            errors.push(FieldError { field, message });
+        }
+
+        if let Some(output_length) = self.output_length {
+            if !(Output::MIN_LENGTH..=Output::MAX_LENGTH).contains(&output_length) {
+                errors.push(FieldError {
+                    field: "output_length",
+                    message: format!(
+                        "output_length must be between {} and {}",
+                        Output::MIN_LENGTH,
+                        Output::MAX_LENGTH
+                    ),
+                });
+            }
+        }
+        if let Some(keyid) = &self.keyid {
+            let keyid = argon2_param_bytes("keyid", keyid).and_then(|keyid| {
+                KeyId::new(&keyid).map_err(|err| Error::InvalidHashOptions(format!("keyid: {err}")))
+            });
+            push_field_error(&mut errors, "keyid", keyid);
+        }
+        if let Some(data) = &self.data {
+            let data = argon2_param_bytes("data", data).and_then(|data| {
+                AssociatedData::new(&data).map_err(|err| Error::InvalidHashOptions(format!("data: {err}")))
+            });
+            push_field_error(&mut errors, "data", data);
+        }
+        if let Some(salt) = &self.salt {
+            let salt = if limits.allow_custom_salt {
+                argon2_custom_salt(salt).map(|_salt| ())
+            } else {
+                Err(Error::InvalidHashOptions("salt requires ALLOW_CUSTOM_SALT".into()))
+            };
+            push_field_error(&mut errors, "salt", salt);
+        }
+        push_field_error(&mut errors, "version", argon2_version(self.version));
+
+        errors
+    }
+
+    // Costs that contradict the preset used to silently win; now they are
+    // refused, so a client can't believe it hashed with a preset it didn't.
+    pub fn validate(&self) -> Result<(), Error> {
+        let Some(name) = self.preset.as_deref() else {
+            return Ok(());
+        };
+        let preset = argon2_preset(name)?;
+
+        // The OWASP costs are for argon2id; argon2i needs more passes to match it.
+        if self.variant != Argon2Variant::Argon2id {
+            return Err(Error::ConflictingOptions(format!(
+                "preset {name} is for argon2id and can't be used with another variant"
+            )));
+        }
+
+        let costs = [
+            ("memory_cost", self.memory_cost, preset.m_cost()),
+            ("time_cost", self.time_cost, preset.t_cost()),
+            ("parallelism", self.parallelism, preset.p_cost()),
+        ];
+        for (field, explicit, expected) in costs {
+            if let Some(explicit) = explicit.filter(|explicit| *explicit != expected) {
+                return Err(Error::ConflictingOptions(format!(
+                    "{field} {explicit} contradicts preset {name}, which sets {expected}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+// A PHC string taken apart for storage in separate columns. The salt and hash
+// are standard padded base64; `keyid` and `data` are kept as in the PHC string.
+#[derive(serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct Argon2HashComponents {
+    pub algorithm: String,
+    pub version: u32,
+    pub memory_cost: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+    pub salt_b64: String,
+    pub hash_b64: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pepper_version: Option<u32>,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct Argon2ComponentsResponse {
+    #[serde(flatten)]
+    pub components: Argon2HashComponents,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<u64>,
+}
+
+// Takes either the PHC `hash` or the `components` `/argon2/hash` returned.
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct Argon2VerifyRequest {
+    pub password: String,
+    #[serde(default)]
+    pub password_encoding: Encoding,
+    pub hash: Option<String>,
+    pub components: Option<Argon2HashComponents>,
+    /// The `context` the hash was made with, if any.
+    pub context: Option<String>,
+    /// Whether the hash was made `pre_hashed`.
+    #[serde(default)]
+    pub pre_hashed: bool,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct Argon2EstimateResponse {
+    pub memory_kib: u32,
+    pub approx_iterations: u64,
+    pub within_worker_limits: bool,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct Argon2VerifyAndAdviseRequest {
+    pub password: String,
+    #[serde(default)]
+    pub password_encoding: Encoding,
+    pub hash: String,
+    pub target_options: Option<Argon2HashOptions>,
+    /// The `context` the hash was made with, if any.
+    pub context: Option<String>,
+    /// Whether the hash was made `pre_hashed`; a replacement is made the same way.
+    #[serde(default)]
+    pub pre_hashed: bool,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct Argon2VerifyAndAdviseResponse {
+    pub result: bool,
+    pub needs_rehash: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_hash: Option<String>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct Argon2VerifySafeRequest {
+    pub password: String,
+    #[serde(default)]
+    pub password_encoding: Encoding,
+    pub hash: Option<String>,
+    /// The `context` the hash was made with, if any.
+    pub context: Option<String>,
+    /// Whether the hash was made `pre_hashed`.
+    #[serde(default)]
+    pub pre_hashed: bool,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct Argon2WarmupResponse {
+    pub warmed: bool,
+    pub elapsed_ms: u64,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct Argon2DeriveKeyRequest {
+    pub password: String,
+    #[serde(default)]
+    pub password_encoding: Encoding,
+    /// Base64, 8 to 64 bytes once decoded; the client keeps it to derive the
+    /// same key again.
+    pub salt: String,
+    /// Bytes of key, between 16 and 64.
+    pub length: usize,
+    /// Costs, variant, version, `keyid` and `data` as for `/argon2/hash`;
+    /// `salt` and `output_length` are given above instead.
+    pub options: Option<Argon2HashOptions>,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct Argon2DeriveKeyResponse {
+    /// Base64.
+    pub key: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct Argon2BenchmarkRequest {
+    /// How long one hash should take, between 1 and 1000.
+    pub target_ms: u64,
+    /// Memory every attempt uses; defaults to, and may not exceed,
+    /// `ARGON2_MAX_MEMORY_KIB`.
+    pub max_memory_kib: Option<u32>,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct Argon2BenchmarkResponse {
+    pub memory_cost: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+    pub measured_ms: u64,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct Argon2DefaultParamsResponse {
+    pub memory_cost: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+    pub output_length: usize,
+    pub version: u32,
+}
+
+// Workers isolates are capped at 128 MB in total, so this is an upper bound:
+// the hash itself must leave room for everything else in the isolate.
+const WORKER_MEMORY_LIMIT_KIB: u32 = 128 * 1024;
+
+// Bounds on `/argon2/benchmark`, which hashes repeatedly within one request:
+// at most this many attempts, stopping early once they add up to the budget,
+// so a search stays well inside the Workers CPU limit.
+const BENCHMARK_MAX_TARGET_MS: u64 = 1000;
+
+// 128 to 512 bit keys, from salts no longer than the PHC format allows.
+const DERIVE_KEY_LENGTHS: std::ops::RangeInclusive<usize> = 16..=64;
+const DERIVE_KEY_MAX_SALT_LEN: usize = 64;
+const BENCHMARK_MAX_ATTEMPTS: u32 = 10;
+const BENCHMARK_BUDGET_MS: u64 = 5000;
+
+// ### Functions
+pub async fn argon2_hash_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let hash_req: HashRequest<Argon2HashOptions> = parse_body(&mut req, config.max_body_bytes).await?;
+    argon2_hash_response(hash_req, config, timing).await
+}
+
+// `/argon2/hash/text`, for `curl` at a terminal: the body is the password
+// itself and the options come from the query string. It has a route of its
+// own because `fetch` labels JSON strings as text/plain, so the content type
+// can't tell the two apart.
+pub async fn argon2_hash_text_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let hash_req = plain_text_hash_request(&mut req, config).await?;
+    argon2_hash_response(hash_req, config, timing).await
+}
+
+async fn argon2_hash_response(
+    hash_req: HashRequest<Argon2HashOptions>,
+    config: &Config,
+    timing: &ServerTiming,
+) -> Result<String, Error> {
+    let output_format = hash_req
+        .options
+        .as_ref()
+        .map(|opts| opts.output_format)
+        .unwrap_or_default();
+    let include_timing = hash_req.include_timing;
+
+    let password_hash = argon2_hash_password(hash_req, config, timing).await?;
+    let elapsed_ms = include_timing.then(|| timing.elapsed_ms());
+
+    if output_format == Argon2OutputFormat::Components {
+        let components_response = Argon2ComponentsResponse {
+            components: argon2_components(&password_hash)?,
+            elapsed_ms,
+        };
+        return serde_json::to_string(&components_response).map_err(|_err| Error::InternalServerError);
+    }
+
+    let hash_response = HashResponse {
+        hash: password_hash,
+        elapsed_ms,
+    };
+    hash_response.to_json(config)
+}
+
+// For `Accept: application/octet-stream`: just the derived key, without the
+// salt and params needed to verify it, so only a caller that chose the salt
+// can make use of it.
+pub async fn argon2_hash_raw_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<Vec<u8>, Error> {
+    let hash_req: HashRequest<Argon2HashOptions> = parse_body(&mut req, config.max_body_bytes).await?;
+    if hash_req.options.as_ref().and_then(|opts| opts.salt.as_ref()).is_none() {
+        return Err(Error::InvalidHashOptions("raw output requires a `salt`".into()));
+    }
+
+    let password_hash = argon2_hash_password(hash_req, config, timing).await?;
+    let (_pepper_version, password_hash) = split_pepper_version(&password_hash)?;
+    let password_hash = PasswordHash::new(password_hash).map_err(|_err| Error::HashFailed)?;
+    let output = password_hash.hash.ok_or(Error::HashFailed)?;
+    Ok(output.as_bytes().to_vec())
+}
+
+// Returns the PHC string, tagged with the pepper version if there is one.
+async fn argon2_hash_password(
+    hash_req: HashRequest<Argon2HashOptions>,
+    config: &Config,
+    timing: &ServerTiming,
+) -> Result<String, Error> {
+    check_pre_hashed(hash_req.pre_hashed, hash_req.min_score, hash_req.reject_breached)?;
+    let password = decode_password(&hash_req.password, hash_req.password_encoding, hash_req.pre_hashed)?;
+    check_password_present(&password, hash_req.allow_empty)?;
+    check_password_control_chars(&password, hash_req.reject_control_chars)?;
+    check_password_length(&password, config.max_password_bytes)?;
+    check_password_strength(&password, hash_req.min_score)?;
+    if hash_req.reject_breached {
+        check_password_breached(&password).await?;
+    }
+    let password = with_context(&password, hash_req.context.as_deref());
+
+    // With a versioned pepper the hash comes back tagged `$pepper$v=<n>`, see
+    // `password::Pepper`.
+    let pepper = config.peppers.current()?;
+    let password = pepper.apply(&password);
+    let password_hash = timing.measure(|| {
+        argon2_hash(
+            &password,
+            hash_req.options,
+            &config.defaults,
+            &config.argon2_limits,
+            config.argon2_secret(),
+        )
+    })
+    .await?;
+    metrics::record_hash("argon2");
+
+    Ok(pepper.tag(password_hash))
+}
+
+// Options as in `?memory_cost=19456&time_cost=2&parallelism=1`. One trailing
+// newline, as left by `echo`, is not part of the password.
+async fn plain_text_hash_request(req: &mut Request, config: &Config) -> Result<HashRequest<Argon2HashOptions>, Error> {
+    let has_query = req
+        .url()
+        .map_err(|_err| Error::BadRequest)?
+        .query()
+        .is_some_and(|query| !query.is_empty());
+    let options = if has_query {
+        Some(req.query().map_err(|err| Error::InvalidBody(err.to_string()))?)
+    } else {
+        None
+    };
+
+    let body = read_text_body(req, config.max_body_bytes).await?;
+    let password = body
+        .strip_suffix("\r\n")
+        .or_else(|| body.strip_suffix('\n'))
+        .unwrap_or(&body)
+        .to_string();
+
+    Ok(HashRequest {
+        password,
+        password_encoding: Encoding::Utf8,
+        options,
+        context: None,
+        pre_hashed: false,
+        include_timing: false,
+        min_score: None,
+        reject_breached: false,
+        allow_empty: false,
+        reject_control_chars: false,
+    })
+}
+
+pub fn argon2_components(hash: &str) -> Result<Argon2HashComponents, Error> {
+    let (pepper_version, hash) = split_pepper_version(hash)?;
+    let password_hash = PasswordHash::new(hash)
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+    let params = Params::try_from(&password_hash)
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+
+    let salt = password_hash.salt.ok_or(Error::InvalidPasswordHash)?;
+    let salt = BASE64_NO_PAD
+        .decode(salt.as_str())
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+    let output = password_hash.hash.ok_or(Error::InvalidPasswordHash)?;
+    let param = |name: &str| password_hash.params.get_str(name).map(str::to_string);
+
+    Ok(Argon2HashComponents {
+        algorithm: password_hash.algorithm.to_string(),
+        version: password_hash.version.unwrap_or(u32::from(Version::default())),
+        memory_cost: params.m_cost(),
+        time_cost: params.t_cost(),
+        parallelism: params.p_cost(),
+        salt_b64: BASE64.encode(salt),
+        hash_b64: BASE64.encode(output.as_bytes()),
+        keyid: param("keyid"),
+        data: param("data"),
+        pepper_version,
+    })
+}
+
+// The inverse of `argon2_components`, including the pepper version prefix.
+pub fn argon2_phc_from_components(components: &Argon2HashComponents) -> Result<String, Error> {
+    let b64 = |field: &str| -> Result<String, Error> {
+        let bytes = BASE64.decode(field).map_err(|_err| Error::InvalidPasswordHash)?;
+        Ok(BASE64_NO_PAD.encode(bytes))
+    };
+
+    let mut params = format!(
+        "m={},t={},p={}",
+        components.memory_cost, components.time_cost, components.parallelism
+    );
+    if let Some(keyid) = &components.keyid {
+        params.push_str(&format!(",keyid={keyid}"));
+    }
+    if let Some(data) = &components.data {
+        params.push_str(&format!(",data={data}"));
+    }
+
+    let phc = format!(
+        "${}$v={}${params}${}${}",
+        components.algorithm,
+        components.version,
+        b64(&components.salt_b64)?,
+        b64(&components.hash_b64)?
+    );
+    Ok(tag_pepper_version(phc, components.pepper_version))
+}
+
+pub fn argon2_hash(
+    password: &[u8],
+    options: Option<Argon2HashOptions>,
+    defaults: &Defaults,
+    limits: &Argon2Limits,
+    secret: &Argon2Secret,
+) -> Result<String, Error> {
+    let salt = options.as_ref().and_then(|opts| opts.salt.clone());
+    let argon2 = argon2_hasher(options, defaults, limits, secret)?;
+    argon2_hash_with(&argon2, password, salt.as_deref())
+}
+
+fn argon2_hasher<'key>(
+    options: Option<Argon2HashOptions>,
+    defaults: &Defaults,
+    limits: &Argon2Limits,
+    secret: &'key Argon2Secret,
+) -> Result<Argon2<'key>, Error> {
+    if let Some(opts) = &options {
+        let field_errors = opts.field_errors(limits);
+        if !field_errors.is_empty() {
+            return Err(Error::ValidationFailed(field_errors));
+        }
+    }
+
+    let (algorithm, version, params) = match options {
+        Some(opts) => (opts.variant.into(), argon2_version(opts.version)?, argon2_params(&opts)?),
+        None => (argon2::Algorithm::default(), Version::default(), defaults.argon2.clone()),
+    };
+    check_argon2_limits(&params, limits)?;
+
+    // Verify tells keyed hashes apart by their keyid alone, so a keyed hash
+    // can't carry any other.
+    match secret.key().zip(secret.keyid.as_deref()) {
+        Some((key, keyid)) => {
+            if !params.keyid().is_empty() {
+                return Err(Error::ConflictingOptions("keyid can't be set while ARGON2_SECRET is".into()));
+            }
+            let params = argon2_params_with_keyid(&params, keyid)?;
+
+            Argon2::new_with_secret(key, algorithm, version, params)
+                .map_err(|err| Error::InvalidConfiguration(format!("ARGON2_SECRET: {err}")))
+        }
+
+        None => Ok(Argon2::new(algorithm, version, params)),
+    }
+}
+
+fn argon2_version(version: Option<u32>) -> Result<Version, Error> {
+    match version {
+        None => Ok(Version::default()),
+        Some(version) => Version::try_from(version)
+            .map_err(|_err| Error::InvalidHashOptions(format!("unknown argon2 version {version}, expected 16 or 19"))),
+    }
+}
+
+fn check_argon2_limits(params: &Params, limits: &Argon2Limits) -> Result<(), Error> {
+    if params.m_cost() > limits.max_memory_kib {
+        return Err(Error::InvalidHashOptions(format!(
+            "memory_cost must be at most {}",
+            limits.max_memory_kib
+        )));
+    }
+    if params.t_cost() > limits.max_time_cost {
+        return Err(Error::InvalidHashOptions(format!(
+            "time_cost must be at most {}",
+            limits.max_time_cost
+        )));
+    }
+    if params.p_cost() > limits.max_parallelism {
+        return Err(Error::InvalidHashOptions(format!(
+            "parallelism must be at most {}",
+            limits.max_parallelism
+        )));
+    }
+
+    Ok(())
+}
+
+fn argon2_params_with_keyid(params: &Params, keyid: &[u8]) -> Result<Params, Error> {
+    let keyid = KeyId::new(keyid).map_err(|_err| Error::HashFailed)?;
+    let data = AssociatedData::new(params.data()).map_err(|_err| Error::HashFailed)?;
+
+    let mut builder = ParamsBuilder::new();
+    builder
+        .m_cost(params.m_cost())
+        .t_cost(params.t_cost())
+        .p_cost(params.p_cost())
+        .keyid(keyid)
+        .data(data);
+    if let Some(output_length) = params.output_len() {
+        builder.output_len(output_length);
+    }
+
+    builder.build().map_err(|_err| Error::HashFailed)
+}
+
+fn argon2_params(opts: &Argon2HashOptions) -> Result<Params, Error> {
+    opts.validate()?;
+    let preset = opts.preset.as_deref().map(argon2_preset).transpose()?;
+
+    let mut builder = ParamsBuilder::new();
+    builder
+        .m_cost(argon2_cost("memory_cost", opts.memory_cost, preset.as_ref().map(Params::m_cost))?)
+        .t_cost(argon2_cost("time_cost", opts.time_cost, preset.as_ref().map(Params::t_cost))?)
+        .p_cost(argon2_cost("parallelism", opts.parallelism, preset.as_ref().map(Params::p_cost))?);
+
+    if let Some(output_length) = opts.output_length {
+        if !(Output::MIN_LENGTH..=Output::MAX_LENGTH).contains(&output_length) {
+            return Err(Error::InvalidHashOptions(format!(
+                "output_length must be between {} and {}",
+                Output::MIN_LENGTH,
+                Output::MAX_LENGTH
+            )));
+        }
+        builder.output_len(output_length);
+    }
+
+    if let Some(keyid) = &opts.keyid {
+        let keyid = KeyId::new(&argon2_param_bytes("keyid", keyid)?)
+            .map_err(|err| Error::InvalidHashOptions(err.to_string()))?;
+        builder.keyid(keyid);
+    }
+
+    if let Some(data) = &opts.data {
+        let data = AssociatedData::new(&argon2_param_bytes("data", data)?)
+            .map_err(|err| Error::InvalidHashOptions(err.to_string()))?;
+        builder.data(data);
+    }
+
+    builder
+        .build()
+        .map_err(|err| Error::InvalidHashOptions(err.to_string()))
+}
+
+// The first two follow the OWASP cheat sheet minimum and RFC 9106's
+// memory-constrained recommendation; `owasp_sensitive` is the most that still
+// leaves headroom under the 128 MB isolate limit.
+fn argon2_preset(preset: &str) -> Result<Params, Error> {
+    match preset {
+        "owasp_min" => Params::new(19 * 1024, 2, 1, None),
+        "owasp_moderate" => Params::new(64 * 1024, 3, 4, None),
+        "owasp_sensitive" => Params::new(96 * 1024, 4, 4, None),
+        _ => return Err(Error::InvalidHashOptions(format!("unknown preset {preset}"))),
+    }
+    .map_err(|err| Error::InvalidHashOptions(err.to_string()))
+}
+
+fn argon2_cost(name: &str, explicit: Option<u32>, preset: Option<u32>) -> Result<u32, Error> {
+    explicit
+        .or(preset)
+        .ok_or_else(|| Error::InvalidHashOptions(format!("{name} is required without a preset")))
+}
+
+fn argon2_param_bytes(name: &str, value: &str) -> Result<Vec<u8>, Error> {
+    BASE64
+        .decode(value)
+        .map_err(|_err| Error::InvalidHashOptions(format!("{name} is not valid base64")))
+}
+
+fn argon2_hash_with(argon2: &Argon2, password: &[u8], salt: Option<&str>) -> Result<String, Error> {
+    let salt = match salt {
+        Some(salt) => argon2_custom_salt(salt)?,
+        None => generate_salt()?,
+    };
+
+    reserve_argon2_memory(argon2.params())?;
+    argon2
+        .hash_password(password, &salt)
+        .map(|password_hash| password_hash.to_string())
+        .map_err(|err| match err {
+            argon2::password_hash::Error::OutputSize { .. }
+            | argon2::password_hash::Error::ParamValueInvalid(_)
+            | argon2::password_hash::Error::SaltInvalid(_) => Error::InvalidHashOptions(err.to_string()),
+            _ => Error::HashFailed,
+        })
+}
+
+// argon2 allocates its working memory with `vec!`, which aborts the whole
+// isolate when the memory can't be had. Reserving as much fallibly first turns
+// that into a retryable 503 instead; the reservation is handed back to the
+// allocator, which reuses it for the hash's own allocation.
+fn reserve_argon2_memory(params: &Params) -> Result<(), Error> {
+    let mut blocks: Vec<argon2::Block> = Vec::new();
+    blocks
+        .try_reserve_exact(params.block_count())
+        .map_err(|_err| Error::ResourceExhausted)
+}
+
+// DANGER: a caller-chosen salt makes the hash deterministic, so equal
+// passwords get equal hashes and precomputed tables work again. This exists
+// only for tests and migrations in controlled environments, and is off unless
+// `ALLOW_CUSTOM_SALT` is set.
+fn argon2_custom_salt(salt: &str) -> Result<SaltString, Error> {
+    // Accepts the PHC salt token as well as padded standard base64.
+    SaltString::from_b64(salt.trim_end_matches('='))
+        .map_err(|err| Error::InvalidHashOptions(format!("salt: {err}")))
+}
+
+pub async fn argon2_hash_batch_handler(
+    mut req: Request,
+    env: &Env,
+    config: &Config,
+    timing: &ServerTiming,
+) -> Result<String, Error> {
+    let idempotency_key = req
+        .headers()
+        .get("Idempotency-Key")
+        .map_err(|_err| Error::BadRequest)?;
+    let (batch_req, body): (BatchHashRequest<Argon2HashOptions>, _) =
+        parse_body_with_raw(&mut req, config.max_body_bytes).await?;
+
+    let pepper = config.peppers.current()?;
+    let idempotency = idempotency_key.and_then(|key| Idempotency::new(env, key, &body, pepper.secret));
+    if let Some(idempotency) = &idempotency {
+        if let Some(response) = idempotency.cached_response().await? {
+            return Ok(response);
+        }
+    }
+
+    if batch_req.passwords.len() > MAX_BATCH_SIZE {
+        return Err(Error::BatchTooLarge);
+    }
+
+    let salt = batch_req.options.as_ref().and_then(|opts| opts.salt.clone());
+    let argon2 = argon2_hasher(batch_req.options, &config.defaults, &config.argon2_limits, config.argon2_secret())?;
+
+    let mut results = Vec::with_capacity(batch_req.passwords.len());
+    for password in &batch_req.passwords {
+        let checked = check_password_present(password.as_bytes(), batch_req.allow_empty)
+            .and_then(|()| check_password_control_chars(password.as_bytes(), batch_req.reject_control_chars))
+            .and_then(|()| check_password_length(password.as_bytes(), config.max_password_bytes));
+        let result = match checked {
+            Ok(()) => {
+                let password = with_context(password.as_bytes(), batch_req.context.as_deref());
+                let password = pepper.apply(&password);
+                timing
+                    .measure(|| argon2_hash_with(&argon2, &password, salt.as_deref()))
+                    .await
+                    .map(|hash| pepper.tag(hash))
+            }
+            Err(err) => Err(err),
+        };
+        results.push(match result {
+            Ok(hash) => {
+                metrics::record_hash("argon2");
+                BatchHashResult::Hash { hash }
+            }
+            Err(err) => BatchHashResult::Error { error: err.message() },
+        });
+    }
+
+    let batch_response = BatchHashResponse { results };
+    let response = serde_json::to_string(&batch_response).map_err(|_err| Error::InternalServerError)?;
+
+    if let Some(idempotency) = &idempotency {
+        idempotency.store_response(&response).await?;
+    }
+
+    Ok(response)
+}
+
+pub async fn argon2id_verify_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let options: Argon2VerifyRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let hash = match (options.hash, &options.components) {
+        (Some(hash), None) => hash,
+        (None, Some(components)) => argon2_phc_from_components(components)?,
+        _ => return Err(Error::InvalidBody("exactly one of `hash` and `components` is required".into())),
+    };
+
+    let password = decode_password(&options.password, options.password_encoding, options.pre_hashed)?;
+    let password = with_context(&password, options.context.as_deref());
+    let (pepper, hash) = config.peppers.for_hash(&hash)?;
+    let password = pepper.apply(&password);
+    let result = timing.measure(|| argon2id_verify(&password, hash, &config.argon2_limits, config.argon2_secret())).await?;
+    metrics::record_verify("argon2", result);
+    let verify_response = VerifyResponse { result };
+    verify_response.to_json(config)
+}
+
+pub fn argon2id_verify(
+    password: &[u8],
+    hash: &str,
+    limits: &Argon2Limits,
+    secret: &Argon2Secret,
+) -> Result<bool, Error> {
+    check_algorithm(hash, Algorithm::Argon2)?;
+    let password_hash = PasswordHash::new(hash)
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+    // The PHC grammar allows leaving the output off, but then nothing matches.
+    if password_hash.hash.is_none() {
+        return Err(Error::InvalidPasswordHash);
+    }
+
+    let algorithm = argon2::Algorithm::try_from(password_hash.algorithm)
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+    // Hashes from before 0x13 (`v=16`) are still around; a missing `v` is 0x13.
+    let version = password_hash
+        .version
+        .map(Version::try_from)
+        .transpose()
+        .map_err(|_err| Error::InvalidPasswordHash)?
+        .unwrap_or_default();
+
+    let params = Params::try_from(&password_hash)
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+    check_argon2_limits(&params, limits)?;
+    reserve_argon2_memory(&params)?;
+
+    // The version, cost, `keyid` and `data` params are taken from the hash
+    // itself, so hashes produced elsewhere with associated data verify as well.
+    // A hash is keyed exactly when it carries the secret's keyid; any other
+    // `keyid` was the client's, and the hash is verified once, unkeyed.
+    let own_keyid = secret.keyid.as_deref().map(|keyid| BASE64_NO_PAD.encode(keyid));
+    let keyed = password_hash.params.get_str("keyid").is_some_and(|keyid| own_keyid.as_deref() == Some(keyid));
+    let argon2 = match (keyed, secret.key()) {
+        (true, Some(key)) => Argon2::new_with_secret(key, algorithm, version, Params::default())
+            .map_err(|_err| Error::VerifyFailed)?,
+        (true, None) => return Err(Error::SigningKeyMissing),
+        (false, _) => Argon2::new(algorithm, version, Params::default()),
+    };
+    argon2_verify_with(&argon2, password, &password_hash)
+}
+
+fn argon2_verify_with(argon2: &Argon2, password: &[u8], password_hash: &PasswordHash) -> Result<bool, Error> {
+    match argon2.verify_password(password, password_hash) {
+        Ok(()) => Ok(true),
+
+        Err(err) => match err {
+            argon2::password_hash::Error::Password => Ok(false),
+            _ => Err(Error::VerifyFailed),
+        },
+    }
+}
+
+// Without a stored hash the password is checked against a dummy one with the
+// default parameters, so a missing user costs as much as a wrong password and
+// timing doesn't reveal which it was.
+pub async fn argon2_verify_safe_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let safe_req: Argon2VerifySafeRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let password = decode_password(&safe_req.password, safe_req.password_encoding, safe_req.pre_hashed)?;
+    let password = with_context(&password, safe_req.context.as_deref());
+    let result = match safe_req.hash.filter(|hash| !hash.is_empty()) {
+        Some(hash) => {
+            let (pepper, hash) = config.peppers.for_hash(&hash)?;
+            let password = pepper.apply(&password);
+            timing.measure(|| argon2id_verify(&password, hash, &config.argon2_limits, config.argon2_secret())).await?
+        }
+        None => {
+            let password = config.peppers.current()?.apply(&password);
+            let dummy_hash = argon2_dummy_hash(&config.defaults.argon2);
+            timing.measure(|| argon2id_verify(&password, &dummy_hash, &config.argon2_limits, &Argon2Secret::default())).await?;
+            false
+        }
+    };
+    metrics::record_verify("argon2", result);
+    let verify_response = VerifyResponse { result };
+    verify_response.to_json(config)
+}
+
+// A well-formed hash no password produces: the salt is fixed and the output is
+// all zeros.
+fn argon2_dummy_hash(params: &Params) -> String {
+    format!(
+        "$argon2id$v={}$m={},t={},p={}$ZHVtbXlkdW1teWR1bW15$AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+        u32::from(Version::default()),
+        params.m_cost(),
+        params.t_cost(),
+        params.p_cost()
+    )
+}
+
+// For a cron trigger keeping isolates warm: one hash with the default
+// parameters, of a fixed password and salt so no client data is involved, and
+// only the time it took is returned.
+pub async fn argon2_warmup_handler(config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let params = config.defaults.argon2.clone();
+    reserve_argon2_memory(&params)?;
+    let argon2 = Argon2::new(argon2::Algorithm::default(), Version::default(), params);
+
+    let mut output = [0u8; Params::DEFAULT_OUTPUT_LEN];
+    timing
+        .measure(|| argon2.hash_password_into(b"warmup", b"cryptoflare-warmup", &mut output))
+        .await
+        .map_err(|_err| Error::HashFailed)?;
+
+    let warmup_response = Argon2WarmupResponse {
+        warmed: true,
+        elapsed_ms: timing.elapsed_ms(),
+    };
+    serde_json::to_string(&warmup_response).map_err(|_err| Error::InternalServerError)
+}
+
+// argon2 as a KDF for client-side encryption: no pepper or `ARGON2_SECRET`,
+// so the client can derive the same key without this Worker.
+pub async fn argon2_derive_key_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let derive_req: Argon2DeriveKeyRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let password = decode(&derive_req.password, derive_req.password_encoding)?;
+    check_password_present(&password, false)?;
+    check_password_length(&password, config.max_password_bytes)?;
+
+    let salt = BASE64
+        .decode(&derive_req.salt)
+        .map_err(|_err| Error::InvalidHashOptions("salt is not valid base64".into()))?;
+    if !(argon2::MIN_SALT_LEN..=DERIVE_KEY_MAX_SALT_LEN).contains(&salt.len()) {
+        return Err(Error::InvalidHashOptions(format!(
+            "salt must be between {} and {DERIVE_KEY_MAX_SALT_LEN} bytes",
+            argon2::MIN_SALT_LEN
+        )));
+    }
+    if !DERIVE_KEY_LENGTHS.contains(&derive_req.length) {
+        return Err(Error::InvalidHashOptions(format!(
+            "length must be between {} and {}",
+            DERIVE_KEY_LENGTHS.start(),
+            DERIVE_KEY_LENGTHS.end()
+        )));
+    }
+
+    let (algorithm, version, params) = match &derive_req.options {
+        Some(opts) if opts.salt.is_some() || opts.output_length.is_some() => {
+            return Err(Error::InvalidHashOptions("give `salt` and `length` outside `options`".into()));
+        }
+        Some(opts) => (opts.variant.into(), argon2_version(opts.version)?, argon2_params(opts)?),
+        None => (argon2::Algorithm::default(), Version::default(), config.defaults.argon2.clone()),
+    };
+    check_argon2_limits(&params, &config.argon2_limits)?;
+    reserve_argon2_memory(&params)?;
+    let argon2 = Argon2::new(algorithm, version, params);
+
+    let mut key = vec![0u8; derive_req.length];
+    timing
+        .measure(|| argon2.hash_password_into(&password, &salt, &mut key))
+        .await
+        .map_err(|err| Error::InvalidHashOptions(err.to_string()))?;
+
+    let derive_key_response = Argon2DeriveKeyResponse { key: BASE64.encode(key) };
+    serde_json::to_string(&derive_key_response).map_err(|_err| Error::InternalServerError)
+}
+
+// Memory is fixed at the most allowed, as OWASP recommends spending on memory
+// before passes, and `time_cost` rises from 1 until a hash takes `target_ms`.
+// Workers only advance the clock across I/O, so each attempt is followed by a
+// zero-length timer before it is read.
+pub async fn argon2_benchmark_handler(mut req: Request, config: &Config) -> Result<String, Error> {
+    let benchmark_req: Argon2BenchmarkRequest = parse_body(&mut req, config.max_body_bytes).await?;
+    if !(1..=BENCHMARK_MAX_TARGET_MS).contains(&benchmark_req.target_ms) {
+        return Err(Error::InvalidBody(format!("target_ms must be between 1 and {BENCHMARK_MAX_TARGET_MS}")));
+    }
+
+    let limits = &config.argon2_limits;
+    let memory_cost = benchmark_req.max_memory_kib.unwrap_or(limits.max_memory_kib);
+    let max_time_cost = limits.max_time_cost.min(BENCHMARK_MAX_ATTEMPTS);
+
+    let mut spent_ms = 0;
+    let mut time_cost = 0;
+    let mut measured_ms = 0;
+    while time_cost < max_time_cost && measured_ms < benchmark_req.target_ms && spent_ms < BENCHMARK_BUDGET_MS {
+        time_cost += 1;
+        let params = Params::new(memory_cost, time_cost, 1, None)
+            .map_err(|err| Error::InvalidHashOptions(err.to_string()))?;
+        check_argon2_limits(&params, limits)?;
+        reserve_argon2_memory(&params)?;
+        let argon2 = Argon2::new(argon2::Algorithm::default(), Version::default(), params);
+
+        let started = Date::now().as_millis();
+        let mut output = [0u8; Params::DEFAULT_OUTPUT_LEN];
+        argon2
+            .hash_password_into(b"benchmark", b"cryptoflare-benchmark", &mut output)
+            .map_err(|_err| Error::HashFailed)?;
+        Delay::from(std::time::Duration::ZERO).await;
+        measured_ms = Date::now().as_millis() - started;
+        spent_ms += measured_ms;
+    }
+
+    let benchmark_response = Argon2BenchmarkResponse {
+        memory_cost,
+        time_cost,
+        parallelism: 1,
+        measured_ms,
+    };
+    serde_json::to_string(&benchmark_response).map_err(|_err| Error::InternalServerError)
+}
+
+// The parameters `/argon2/hash` uses when no options are given: the argon2
+// crate's defaults unless overridden through configuration.
+pub fn argon2_default_params_handler(config: &Config) -> Result<String, Error> {
+    let params = &config.defaults.argon2;
+
+    let params_response = Argon2DefaultParamsResponse {
+        memory_cost: params.m_cost(),
+        time_cost: params.t_cost(),
+        parallelism: params.p_cost(),
+        output_length: params.output_len().unwrap_or(Params::DEFAULT_OUTPUT_LEN),
+        version: Version::default().into(),
+    };
+    serde_json::to_string(&params_response).map_err(|_err| Error::InternalServerError)
+}
+
+// Reports what the options would cost without hashing anything.
+pub async fn argon2_estimate_handler(mut req: Request, config: &Config) -> Result<String, Error> {
+    let options: Argon2HashOptions = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let params = argon2_params(&options)?;
+
+    let estimate_response = Argon2EstimateResponse {
+        memory_kib: params.m_cost(),
+        // each pass fills every 1 KiB block of memory once
+        approx_iterations: u64::from(params.t_cost()) * u64::from(params.m_cost()),
+        within_worker_limits: params.m_cost() < WORKER_MEMORY_LIMIT_KIB,
+    };
+    serde_json::to_string(&estimate_response).map_err(|_err| Error::InternalServerError)
+}
+
+pub async fn argon2_needs_rehash_handler(mut req: Request, config: &Config) -> Result<String, Error> {
+    let rehash_req: NeedsRehashRequest<Argon2HashOptions> = parse_body(&mut req, config.max_body_bytes).await?;
+
+    // A hash made with an older pepper is due for one as well.
+    let (pepper_version, hash) = split_pepper_version(&rehash_req.hash)?;
+    let needs_rehash = pepper_version != config.peppers.current_version
+        || argon2_needs_rehash(hash, rehash_req.options, &config.defaults)?;
+    let rehash_response = NeedsRehashResponse { needs_rehash };
+    serde_json::to_string(&rehash_response).map_err(|_err| Error::InternalServerError)
+}
+
+fn argon2_needs_rehash(hash: &str, options: Option<Argon2HashOptions>, defaults: &Defaults) -> Result<bool, Error> {
+    let password_hash = PasswordHash::new(hash)
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+
+    let algorithm = argon2::Algorithm::try_from(password_hash.algorithm)
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+    let stored = Params::try_from(&password_hash)
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+
+    let version = password_hash.version.unwrap_or(u32::from(Version::default()));
+
+    let (target_algorithm, target_version, target) = match options {
+        Some(opts) => (opts.variant.into(), argon2_version(opts.version)?, argon2_params(&opts)?),
+        None => (argon2::Algorithm::default(), Version::default(), defaults.argon2.clone()),
+    };
+
+    Ok(algorithm != target_algorithm
+        || version != u32::from(target_version)
+        || stored.m_cost() < target.m_cost()
+        || stored.t_cost() < target.t_cost()
+        || stored.p_cost() < target.p_cost())
+}
+
+pub async fn argon2_verify_and_advise_handler(
+    mut req: Request,
+    config: &Config,
+    timing: &ServerTiming,
+) -> Result<String, Error> {
+    let advise_req: Argon2VerifyAndAdviseRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let password = decode_password(&advise_req.password, advise_req.password_encoding, advise_req.pre_hashed)?;
+    let password = with_context(&password, advise_req.context.as_deref());
+    let (pepper, hash) = config.peppers.for_hash(&advise_req.hash)?;
+    let peppered = pepper.apply(&password);
+    let result = timing.measure(|| argon2id_verify(&peppered, hash, &config.argon2_limits, config.argon2_secret())).await?;
+    metrics::record_verify("argon2", result);
+
+    // Only a caller holding the right password gets a replacement hash, so a
+    // failed verification never pays for a second hash. The replacement is
+    // always made with the current pepper.
+    let needs_rehash = result
+        && (pepper.version != config.peppers.current_version
+            || argon2_needs_rehash(hash, advise_req.target_options.clone(), &config.defaults)?);
+    let new_hash = if needs_rehash {
+        let pepper = config.peppers.current()?;
+        let password = pepper.apply(&password);
+        let new_hash = timing.measure(|| {
+            argon2_hash(
+                &password,
+                advise_req.target_options,
+                &config.defaults,
+                &config.argon2_limits,
+                config.argon2_secret(),
+            )
+        })
+        .await?;
+        metrics::record_hash("argon2");
+        Some(pepper.tag(new_hash))
+    } else {
+        None
+    };
+
+    let advise_response = Argon2VerifyAndAdviseResponse {
+        result,
+        needs_rehash,
+        new_hash,
+    };
+    serde_json::to_string(&advise_response).map_err(|_err| Error::InternalServerError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // As configured with `ARGON2_SECRET` and `ARGON2_SECRET_ID`.
+    fn secret() -> Argon2Secret {
+        Argon2Secret {
+            key: Some("argon2 test secret".into()),
+            keyid: Some(b"test-k1".to_vec()),
+        }
+    }
+
+    // `ARGON2_SECRET_ID` left behind after `ARGON2_SECRET` was removed.
+    fn keyid_only() -> Argon2Secret {
+        Argon2Secret {
+            key: None,
+            keyid: Some(b"test-k1".to_vec()),
+        }
+    }
+
+    fn no_secret() -> Argon2Secret {
+        Argon2Secret::default()
+    }
+
+    fn defaults() -> Defaults {
+        Defaults {
+            argon2: Params::new(64, 1, 1, None).unwrap(),
+            bcrypt_work_factor: 4,
+        }
+    }
+
+    fn limits() -> Argon2Limits {
+        Argon2Limits {
+            max_memory_kib: 64 * 1024,
+            max_time_cost: 10,
+            max_parallelism: 8,
+            allow_custom_salt: false,
+        }
+    }
+
+    fn options(json: serde_json::Value) -> Argon2HashOptions {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn hash(options: Option<Argon2HashOptions>, secret: &Argon2Secret) -> String {
+        argon2_hash(b"hunter2", options, &defaults(), &limits(), secret).ok().unwrap()
+    }
+
+    fn verify(password: &[u8], hash: &str, secret: &Argon2Secret) -> Option<bool> {
+        argon2id_verify(password, hash, &limits(), secret).ok()
+    }
+
+    #[test]
+    fn hashes_verify_with_the_right_password_only() {
+        let hash = hash(None, &no_secret());
+        assert!(hash.starts_with("$argon2id$v=19$"));
+        assert_eq!(verify(b"hunter2", &hash, &no_secret()), Some(true));
+        assert_eq!(verify(b"hunter3", &hash, &no_secret()), Some(false));
+    }
+
+    #[test]
+    fn malformed_hashes_are_rejected() {
+        for hash in ["$argon2id$v=19$m=64,t=1,p=1$c2FsdHNhbHQ$!!", "$argon2id$v=19$not-a-hash"] {
+            let err = argon2id_verify(b"hunter2", hash, &limits(), &no_secret()).err().unwrap();
+            assert_eq!(err.code(), "INVALID_PASSWORD_HASH");
+        }
+    }
+
+    #[test]
+    fn stored_costs_past_the_limits_are_rejected() {
+        for hash in [
+            "$argon2id$v=19$m=64,t=4294967295,p=1$c2FsdHNhbHQ$aGFzaGhhc2hoYXNoaGFzaGhhc2hoYXNoaGFzaA",
+            "$argon2id$v=19$m=4194304,t=1,p=1$c2FsdHNhbHQ$aGFzaGhhc2hoYXNoaGFzaGhhc2hoYXNoaGFzaA",
+            "$argon2id$v=19$m=1024,t=1,p=64$c2FsdHNhbHQ$aGFzaGhhc2hoYXNoaGFzaGhhc2hoYXNoaGFzaA",
+        ] {
+            let err = argon2id_verify(b"hunter2", hash, &limits(), &no_secret()).err().unwrap();
+            assert_eq!(err.code(), "INVALID_HASH_OPTIONS");
+        }
+    }
+
+    #[test]
+    fn invalid_options_are_rejected() {
+        let options = options(serde_json::json!({ "memory_cost": 64, "parallelism": 1 }));
+        let err = argon2_hash(b"hunter2", Some(options), &defaults(), &limits(), &no_secret()).err().unwrap();
+        assert_eq!(err.code(), "VALIDATION_FAILED");
+    }
+
+    #[test]
+    fn options_contradicting_the_preset_conflict() {
+        for json in [
+            serde_json::json!({ "preset": "owasp_min", "memory_cost": 65536 }),
+            serde_json::json!({ "preset": "owasp_moderate", "time_cost": 3, "parallelism": 1 }),
+            serde_json::json!({ "preset": "owasp_min", "variant": "argon2i" }),
+        ] {
+            let err = options(json).validate().err().unwrap();
+            assert_eq!(err.code(), "CONFLICTING_OPTIONS");
+        }
+    }
+
+    #[test]
+    fn options_matching_the_preset_do_not_conflict() {
+        let options = options(serde_json::json!({ "preset": "owasp_min", "memory_cost": 19456, "time_cost": 2 }));
+        assert!(options.validate().is_ok());
+        assert!(argon2_hash(b"hunter2", Some(options), &defaults(), &limits(), &no_secret()).is_ok());
+    }
+
+    #[test]
+    fn keyed_hashes_only_verify_with_the_secret() {
+        let keyed = hash(None, &secret());
+        assert!(keyed.contains(&format!("keyid={}", BASE64_NO_PAD.encode(b"test-k1"))));
+        assert_eq!(verify(b"hunter2", &keyed, &secret()), Some(true));
+        assert_eq!(verify(b"hunter3", &keyed, &secret()), Some(false));
+
+        let err = argon2id_verify(b"hunter2", &keyed, &limits(), &keyid_only()).err().unwrap();
+        assert_eq!(err.code(), "SIGNING_KEY_MISSING");
+    }
+
+    #[test]
+    fn unkeyed_hashes_verify_with_or_without_the_secret() {
+        let unkeyed = hash(None, &no_secret());
+        assert_eq!(verify(b"hunter2", &unkeyed, &no_secret()), Some(true));
+        assert_eq!(verify(b"hunter2", &unkeyed, &secret()), Some(true));
+    }
+
+    #[test]
+    fn a_client_keyid_does_not_make_a_hash_keyed() {
+        let client_keyid = || {
+            Some(options(serde_json::json!({
+                "memory_cost": 64, "time_cost": 1, "parallelism": 1, "keyid": "AQIDBA==",
+            })))
+        };
+
+        let unkeyed = hash(client_keyid(), &no_secret());
+        assert_eq!(verify(b"hunter2", &unkeyed, &no_secret()), Some(true));
+        assert_eq!(verify(b"hunter2", &unkeyed, &secret()), Some(true));
+        assert_eq!(verify(b"hunter3", &unkeyed, &secret()), Some(false));
+        assert_eq!(verify(b"hunter2", &unkeyed, &keyid_only()), Some(true));
+    }
+
+    #[test]
+    fn keyed_hashes_can_only_carry_the_secrets_keyid() {
+        let options = options(serde_json::json!({
+            "memory_cost": 64, "time_cost": 1, "parallelism": 1, "keyid": "AQIDBA==",
+        }));
+        let err = argon2_hash(b"hunter2", Some(options), &defaults(), &limits(), &secret()).err().unwrap();
+        assert_eq!(err.code(), "CONFLICTING_OPTIONS");
+    }
+
+    #[test]
+    fn output_lengths_round_trip_through_verify() {
+        for output_length in [32, 64] {
+            let hash = hash(
+                Some(options(serde_json::json!({
+                    "memory_cost": 64, "time_cost": 1, "parallelism": 1, "output_length": output_length,
+                }))),
+                &no_secret(),
+            );
+            let output = PasswordHash::new(&hash).ok().unwrap().hash.unwrap();
+            assert_eq!(output.len(), output_length);
+            assert_eq!(verify(b"hunter2", &hash, &no_secret()), Some(true));
+            assert_eq!(verify(b"hunter3", &hash, &no_secret()), Some(false));
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Auth is enabled by setting the `API_KEY` secret; requests must then carry
+//! `Authorization: Bearer <API_KEY>`. Rejections name the scheme, and the
+//! `AUTH_REALM` variable if set, in `WWW-Authenticate`.
+
+use subtle::ConstantTimeEq;
+use worker::{Env, Request};
+
+use crate::error::Error;
+
+pub fn authenticate(req: &Request, env: &Env) -> Result<(), Error> {
+    let api_key = match env.secret("API_KEY") {
+        Ok(secret) => secret.to_string(),
+        Err(_err) => return Ok(()),
+    };
+
+    let unauthorized = || Error::Unauthorized {
+        realm: env.var("AUTH_REALM").ok().map(|var| var.to_string()),
+    };
+
+    let authorization = req
+        .headers()
+        .get("Authorization")
+        .map_err(|_err| unauthorized())?
+        .ok_or_else(unauthorized)?;
+
+    let token = authorization
+        .strip_prefix("Bearer ")
+        .ok_or_else(unauthorized)?;
+
+    if bool::from(token.as_bytes().ct_eq(api_key.as_bytes())) {
+        Ok(())
+    } else {
+        Err(unauthorized())
+    }
+}
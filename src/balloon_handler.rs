@@ -0,0 +1,157 @@
+//! Balloon hashing and verification.
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier};
+use balloon_hash::Balloon;
+use sha2::{Digest, Sha256, Sha512};
+use worker::Request;
+
+use crate::body::parse_body;
+use crate::config::{BalloonLimits, Config};
+use crate::error::Error;
+use crate::metrics;
+use crate::password::{
+    check_password_breached, check_password_control_chars, check_password_length,
+    check_password_present, check_password_strength, check_pre_hashed, decode_password, with_context,
+};
+use crate::random_handler::generate_salt;
+use crate::server_timing::ServerTiming;
+use crate::types::{HashRequest, HashResponse, VerifyRequest, VerifyResponse};
+use crate::verify_handler::{check_algorithm, Algorithm};
+
+// ### Types
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct BalloonHashOptions {
+    pub space_cost: u32,
+    pub time_cost: u32,
+    pub hash_function: Option<BalloonHashFunction>,
+}
+
+#[derive(serde::Deserialize, Default, Clone, Copy, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BalloonHashFunction {
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+// ### Functions
+pub async fn balloon_hash_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let hash_req: HashRequest<BalloonHashOptions> = parse_body(&mut req, config.max_body_bytes).await?;
+
+    check_pre_hashed(hash_req.pre_hashed, hash_req.min_score, hash_req.reject_breached)?;
+    let password = decode_password(&hash_req.password, hash_req.password_encoding, hash_req.pre_hashed)?;
+    check_password_present(&password, hash_req.allow_empty)?;
+    check_password_control_chars(&password, hash_req.reject_control_chars)?;
+    check_password_length(&password, config.max_password_bytes)?;
+    check_password_strength(&password, hash_req.min_score)?;
+    if hash_req.reject_breached {
+        check_password_breached(&password).await?;
+    }
+    let password = with_context(&password, hash_req.context.as_deref());
+
+    let pepper = config.peppers.current()?;
+    let password = pepper.apply(&password);
+    let password_hash = timing.measure(|| balloon_hash(&password, hash_req.options, &config.balloon_limits)).await?;
+    metrics::record_hash("balloon");
+    let elapsed_ms = hash_req
+        .include_timing
+        .then(|| timing.elapsed_ms());
+
+    let hash_response = HashResponse {
+        hash: pepper.tag(password_hash),
+        elapsed_ms,
+    };
+    hash_response.to_json(config)
+}
+
+pub fn balloon_hash(
+    password: &[u8],
+    options: Option<BalloonHashOptions>,
+    limits: &BalloonLimits,
+) -> Result<String, Error> {
+    let salt = generate_salt()?;
+
+    let (hash_function, params) = match options {
+        Some(opts) => {
+            let params = balloon_hash::Params::new(opts.space_cost, opts.time_cost, 1)
+                .map_err(|err| Error::InvalidHashOptions(err.to_string()))?;
+            check_balloon_limits(&params, limits)?;
+
+            (opts.hash_function.unwrap_or_default(), params)
+        }
+
+        None => (BalloonHashFunction::default(), balloon_hash::Params::default()),
+    };
+
+    let password_hash = match hash_function {
+        BalloonHashFunction::Sha256 => Balloon::<Sha256>::from(params).hash_password(password, &salt),
+        BalloonHashFunction::Sha512 => Balloon::<Sha512>::from(params).hash_password(password, &salt),
+    };
+
+    password_hash
+        .map(|password_hash| password_hash.to_string())
+        .map_err(|_err| Error::HashFailed)
+}
+
+pub async fn balloon_verify_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let options: VerifyRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let password = decode_password(&options.password, options.password_encoding, options.pre_hashed)?;
+    let password = with_context(&password, options.context.as_deref());
+    let (pepper, hash) = config.peppers.for_hash(&options.hash)?;
+    let password = pepper.apply(&password);
+    let result = timing.measure(|| balloon_verify(&password, hash, &config.balloon_limits)).await?;
+    metrics::record_verify("balloon", result);
+    let verify_response = VerifyResponse { result };
+    verify_response.to_json(config)
+}
+
+// The PHC string doesn't name the digest, but its output length gives it away.
+pub fn balloon_verify(password: &[u8], hash: &str, limits: &BalloonLimits) -> Result<bool, Error> {
+    check_algorithm(hash, Algorithm::Balloon)?;
+    let password_hash = PasswordHash::new(hash)
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+
+    if balloon_hash::Algorithm::try_from(password_hash.algorithm).is_err() {
+        return Err(Error::InvalidPasswordHash);
+    }
+    let params = balloon_hash::Params::try_from(&password_hash)
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+    check_balloon_limits(&params, limits)?;
+
+    let output_len = password_hash.hash.map(|output| output.len());
+    let verified = if output_len == Some(Sha256::output_size()) {
+        Balloon::<Sha256>::default().verify_password(password, &password_hash)
+    } else if output_len == Some(Sha512::output_size()) {
+        Balloon::<Sha512>::default().verify_password(password, &password_hash)
+    } else {
+        return Err(Error::InvalidPasswordHash);
+    };
+
+    match verified {
+        Ok(()) => Ok(true),
+
+        Err(err) => match err {
+            argon2::password_hash::Error::Password => Ok(false),
+            _ => Err(Error::VerifyFailed),
+        },
+    }
+}
+
+fn check_balloon_limits(params: &balloon_hash::Params, limits: &BalloonLimits) -> Result<(), Error> {
+    if params.s_cost.get() > limits.max_space_cost {
+        return Err(Error::InvalidHashOptions(format!(
+            "space_cost must be at most {}",
+            limits.max_space_cost
+        )));
+    }
+    if params.t_cost.get() > limits.max_time_cost {
+        return Err(Error::InvalidHashOptions(format!(
+            "time_cost must be at most {}",
+            limits.max_time_cost
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,310 @@
+//! bcrypt and bcrypt-sha256 hashing, verification and rehash checks.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use sha2::{Digest, Sha256};
+use worker::Request;
+
+use crate::body::parse_body;
+use crate::config::{Config, Defaults};
+use crate::error::{push_field_error, Error, FieldError};
+use crate::metrics;
+use crate::password::{
+    check_password_breached, check_password_control_chars, check_password_length,
+    check_password_present, check_password_strength, check_pre_hashed, decode_password, split_pepper_version,
+    with_context,
+};
+use crate::server_timing::ServerTiming;
+use crate::types::{
+    HashRequest, HashResponse, NeedsRehashRequest, NeedsRehashResponse, VerifyRequest,
+    VerifyResponse,
+};
+use crate::verify_handler::{check_algorithm, Algorithm};
+
+// ### Types
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct BcryptHashOptions {
+    pub work_factor: u32,
+    /// Always on for `/bcrypt-sha256/hash`; such hashes only verify through
+    /// `/bcrypt-sha256/verify`.
+    #[serde(default)]
+    pub pre_hash_sha256: bool,
+    /// `2a`, `2b` (the default) or `2y`; only the prefix differs.
+    pub variant: Option<String>,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct BcryptDefaultParamsResponse {
+    pub work_factor: u32,
+}
+
+impl BcryptHashOptions {
+    // Every problem with the fields at once, as `Argon2HashOptions::field_errors`.
+    pub fn field_errors(&self, max_work_factor: u32) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        push_field_error(&mut errors, "work_factor", check_bcrypt_work_factor(self.work_factor, max_work_factor));
+        push_field_error(&mut errors, "variant", bcrypt_version(self.variant.as_deref()));
+        errors
+    }
+}
+
+// The cost is a 5 bit exponent, and below 4 the crate refuses to hash.
+// `BCRYPT_MAX_WORK_FACTOR` lowers the top: each step doubles the time.
+pub const BCRYPT_WORK_FACTORS: std::ops::RangeInclusive<u32> = 4..=31;
+
+// bcrypt silently ignores everything past the 72nd byte
+pub const BCRYPT_MAX_PASSWORD_BYTES: usize = 72;
+
+// ### Functions
+pub async fn bcrypt_hash_handler(
+    mut req: Request,
+    config: &Config,
+    timing: &ServerTiming,
+    pre_hash_sha256: bool,
+) -> Result<String, Error> {
+    let hash_req: HashRequest<BcryptHashOptions> = parse_body(&mut req, config.max_body_bytes).await?;
+    let pre_hash_sha256 = pre_hash_sha256
+        || hash_req
+            .options
+            .as_ref()
+            .is_some_and(|opts| opts.pre_hash_sha256);
+
+    check_pre_hashed(hash_req.pre_hashed, hash_req.min_score, hash_req.reject_breached)?;
+    let password = decode_password(&hash_req.password, hash_req.password_encoding, hash_req.pre_hashed)?;
+    check_password_present(&password, hash_req.allow_empty)?;
+    check_password_control_chars(&password, hash_req.reject_control_chars)?;
+    check_password_length(&password, config.max_password_bytes)?;
+    check_password_strength(&password, hash_req.min_score)?;
+    if hash_req.reject_breached {
+        check_password_breached(&password).await?;
+    }
+    let password = with_context(&password, hash_req.context.as_deref());
+    if !pre_hash_sha256 && password.len() > BCRYPT_MAX_PASSWORD_BYTES {
+        return Err(Error::PasswordTooLongForBcrypt);
+    }
+
+    let pepper = config.peppers.current()?;
+    let password = pepper.apply(&password);
+    let password = if pre_hash_sha256 { bcrypt_pre_hash(&password) } else { password };
+    let password_hash = timing.measure(|| bcrypt_hash(&password, hash_req.options, &config.defaults, config.bcrypt_max_work_factor)).await?;
+    metrics::record_hash(bcrypt_name(pre_hash_sha256));
+    let elapsed_ms = hash_req
+        .include_timing
+        .then(|| timing.elapsed_ms());
+
+    let hash_response = HashResponse {
+        hash: pepper.tag(password_hash),
+        elapsed_ms,
+    };
+    hash_response.to_json(config)
+}
+
+// bcrypt-sha256: the base64 SHA-256 digest is 44 bytes, well within bcrypt's
+// 72 byte limit, so long passphrases are no longer silently truncated.
+pub fn bcrypt_pre_hash(password: &[u8]) -> Vec<u8> {
+    BASE64.encode(Sha256::digest(password)).into_bytes()
+}
+
+pub fn bcrypt_name(pre_hash_sha256: bool) -> &'static str {
+    if pre_hash_sha256 { "bcrypt-sha256" } else { "bcrypt" }
+}
+
+// The default work factor was checked against the maximum with the rest of
+// the configuration.
+pub fn bcrypt_hash(
+    password: &[u8],
+    options: Option<BcryptHashOptions>,
+    defaults: &Defaults,
+    max_work_factor: u32,
+) -> Result<String, Error> {
+    let (work_factor, version) = match options {
+        Some(opts) => {
+            let field_errors = opts.field_errors(max_work_factor);
+            if !field_errors.is_empty() {
+                return Err(Error::ValidationFailed(field_errors));
+            }
+            (opts.work_factor, bcrypt_version(opts.variant.as_deref())?)
+        }
+        None => (defaults.bcrypt_work_factor, bcrypt::Version::TwoB),
+    };
+
+    let hash_parts = bcrypt::hash_with_result(password, work_factor).map_err(|err| match err {
+        bcrypt::BcryptError::CostNotAllowed(_) => Error::InvalidHashOptions(err.to_string()),
+        bcrypt::BcryptError::Rand(_) => Error::RandomUnavailable,
+        _ => Error::HashFailed,
+    })?;
+
+    Ok(hash_parts.format_for_version(version))
+}
+
+fn check_bcrypt_work_factor(work_factor: u32, max_work_factor: u32) -> Result<(), Error> {
+    if !BCRYPT_WORK_FACTORS.contains(&work_factor) {
+        return Err(Error::InvalidHashOptions(format!(
+            "work_factor must be between {} and {}",
+            BCRYPT_WORK_FACTORS.start(),
+            BCRYPT_WORK_FACTORS.end()
+        )));
+    }
+    if work_factor > max_work_factor {
+        return Err(Error::InvalidHashOptions(format!("work_factor must be at most {max_work_factor}")));
+    }
+
+    Ok(())
+}
+
+// PHP's `password_hash` emits `$2y$`; the algorithm behind all three is the same.
+fn bcrypt_version(variant: Option<&str>) -> Result<bcrypt::Version, Error> {
+    match variant {
+        None | Some("2b") => Ok(bcrypt::Version::TwoB),
+        Some("2a") => Ok(bcrypt::Version::TwoA),
+        Some("2y") => Ok(bcrypt::Version::TwoY),
+        Some(variant) => Err(Error::InvalidHashOptions(format!("unknown bcrypt variant {variant}"))),
+    }
+}
+
+pub async fn bcrypt_verify_handler(
+    mut req: Request,
+    config: &Config,
+    timing: &ServerTiming,
+    pre_hash_sha256: bool,
+) -> Result<String, Error> {
+    let options: VerifyRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let password = decode_password(&options.password, options.password_encoding, options.pre_hashed)?;
+    let password = with_context(&password, options.context.as_deref());
+    let (pepper, hash) = config.peppers.for_hash(&options.hash)?;
+    let password = pepper.apply(&password);
+    let password = if pre_hash_sha256 { bcrypt_pre_hash(&password) } else { password };
+    let result = timing.measure(|| bcrypt_verify(&password, hash, config.bcrypt_max_work_factor)).await?;
+    metrics::record_verify(bcrypt_name(pre_hash_sha256), result);
+    let verify_response = VerifyResponse { result };
+    verify_response.to_json(config)
+}
+
+pub fn bcrypt_verify(password: &[u8], hash: &str, max_work_factor: u32) -> Result<bool, Error> {
+    check_algorithm(hash, Algorithm::Bcrypt)?;
+    let hash_parts: bcrypt::HashParts = hash
+        .parse()
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+    if hash_parts.get_cost() > max_work_factor {
+        return Err(Error::InvalidHashOptions(format!("work_factor must be at most {max_work_factor}")));
+    }
+
+    // A mismatch is `Ok(false)`; only failures unrelated to the hash's format
+    // are server errors.
+    bcrypt::verify(password, hash).map_err(|err| match err {
+        bcrypt::BcryptError::CostNotAllowed(_)
+        | bcrypt::BcryptError::InvalidCost(_)
+        | bcrypt::BcryptError::InvalidPrefix(_)
+        | bcrypt::BcryptError::InvalidHash(_)
+        | bcrypt::BcryptError::InvalidSaltLen(_)
+        | bcrypt::BcryptError::InvalidBase64(_) => Error::InvalidPasswordHash,
+        _ => Error::VerifyFailed,
+    })
+}
+
+// `bcrypt::DEFAULT_COST` unless overridden through configuration.
+pub fn bcrypt_default_params_handler(config: &Config) -> Result<String, Error> {
+    let params_response = BcryptDefaultParamsResponse {
+        work_factor: config.defaults.bcrypt_work_factor,
+    };
+    serde_json::to_string(&params_response).map_err(|_err| Error::InternalServerError)
+}
+
+pub async fn bcrypt_needs_rehash_handler(mut req: Request, config: &Config) -> Result<String, Error> {
+    let rehash_req: NeedsRehashRequest<BcryptHashOptions> = parse_body(&mut req, config.max_body_bytes).await?;
+
+    // A hash made with an older pepper is due for one as well.
+    let (pepper_version, hash) = split_pepper_version(&rehash_req.hash)?;
+    let needs_rehash = pepper_version != config.peppers.current_version
+        || bcrypt_needs_rehash(hash, rehash_req.options, &config.defaults)?;
+    let rehash_response = NeedsRehashResponse { needs_rehash };
+    serde_json::to_string(&rehash_response).map_err(|_err| Error::InternalServerError)
+}
+
+fn bcrypt_needs_rehash(hash: &str, options: Option<BcryptHashOptions>, defaults: &Defaults) -> Result<bool, Error> {
+    let hash_parts: bcrypt::HashParts = hash
+        .parse()
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+
+    let target_cost = match options {
+        Some(opts) => opts.work_factor,
+        None => defaults.bcrypt_work_factor,
+    };
+
+    Ok(hash_parts.get_cost() < target_cost)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> Defaults {
+        Defaults {
+            argon2: argon2::Params::default(),
+            bcrypt_work_factor: 4,
+        }
+    }
+
+    fn options(json: serde_json::Value) -> BcryptHashOptions {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn hashes_verify_with_the_right_password_only() {
+        let hash = bcrypt_hash(b"hunter2", None, &defaults(), 14).ok().unwrap();
+        assert!(hash.starts_with("$2b$04$"));
+        assert_eq!(bcrypt_verify(b"hunter2", &hash, 14).ok(), Some(true));
+        assert_eq!(bcrypt_verify(b"hunter3", &hash, 14).ok(), Some(false));
+    }
+
+    #[test]
+    fn malformed_hashes_are_rejected() {
+        let err = bcrypt_verify(b"hunter2", "$2b$04$not-a-hash", 14).err().unwrap();
+        assert_eq!(err.code(), "INVALID_PASSWORD_HASH");
+    }
+
+    #[test]
+    fn php_2y_hashes_verify() {
+        let options = options(serde_json::json!({ "work_factor": 4, "variant": "2y" }));
+        let hash = bcrypt_hash(b"hunter2", Some(options), &defaults(), 14).ok().unwrap();
+        assert!(hash.starts_with("$2y$04$"));
+        assert_eq!(bcrypt_verify(b"hunter2", &hash, 14).ok(), Some(true));
+        assert_eq!(bcrypt_verify(b"hunter3", &hash, 14).ok(), Some(false));
+
+        // The example from PHP's `password_verify` documentation.
+        let php_hash = "$2y$10$.vGA1O9wmRjrwAVXD98HNOgsNpDczlqm3Jq7KnEd1rVAGv3Fykk1a";
+        assert_eq!(bcrypt_verify(b"rasmuslerdorf", php_hash, 14).ok(), Some(true));
+    }
+
+    #[test]
+    fn work_factors_outside_4_to_31_are_rejected() {
+        for (work_factor, valid) in [(3, false), (4, true), (31, true), (32, false)] {
+            match check_bcrypt_work_factor(work_factor, 31) {
+                Ok(()) => assert!(valid, "{work_factor} accepted"),
+                Err(err) => {
+                    assert!(!valid, "{work_factor} rejected");
+                    assert_eq!(err.code(), "INVALID_HASH_OPTIONS");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn work_factors_past_the_maximum_are_rejected() {
+        let options = options(serde_json::json!({ "work_factor": 15 }));
+        let err = bcrypt_hash(b"hunter2", Some(options), &defaults(), 14).err().unwrap();
+        assert_eq!(err.code(), "VALIDATION_FAILED");
+
+        let stored = "$2b$31$abcdefghijklmnopqrstuuAd2NuvjYDzLoD2mOE9rZZ5ylGpx4Ywm";
+        let err = bcrypt_verify(b"hunter2", stored, 14).err().unwrap();
+        assert_eq!(err.code(), "INVALID_HASH_OPTIONS");
+    }
+
+    #[test]
+    fn invalid_options_are_rejected() {
+        let options = options(serde_json::json!({ "work_factor": 4, "variant": "2x" }));
+        let err = bcrypt_hash(b"hunter2", Some(options), &defaults(), 14).err().unwrap();
+        assert_eq!(err.code(), "VALIDATION_FAILED");
+    }
+}
@@ -0,0 +1,134 @@
+//! Bodies are JSON or `application/x-www-form-urlencoded`, where nested options
+//! are written as `options[work_factor]=12` (brackets may be percent-encoded).
+//! serde's message names the offending field (e.g. "unknown field `work_fator`"),
+//! so it is passed on to the client. Bodies may be gzip or deflate compressed.
+
+use std::io::Read;
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use futures_util::StreamExt;
+use worker::Request;
+
+use crate::error::Error;
+
+#[derive(Clone, Copy)]
+enum BodyFormat {
+    Json,
+    Form,
+}
+
+pub async fn parse_body<T: serde::de::DeserializeOwned>(req: &mut Request, max_body_bytes: usize) -> Result<T, Error> {
+    parse_body_with_raw(req, max_body_bytes).await.map(|(parsed, _body)| parsed)
+}
+
+// Also hands back the raw bytes, for handlers that fingerprint the request.
+pub async fn parse_body_with_raw<T: serde::de::DeserializeOwned>(
+    req: &mut Request,
+    max_body_bytes: usize,
+) -> Result<(T, Vec<u8>), Error> {
+    let format = match media_type(req)?.as_str() {
+        // `fetch` labels string bodies as text/plain, so those stay JSON too.
+        "" | "application/json" | "text/plain" => BodyFormat::Json,
+        "application/x-www-form-urlencoded" => BodyFormat::Form,
+        _ => return Err(Error::UnsupportedMediaType),
+    };
+
+    let body = read_decoded_body(req, max_body_bytes).await?;
+    let text = std::str::from_utf8(&body).map_err(|_err| Error::BadRequest)?;
+
+    let parsed = match format {
+        BodyFormat::Json => serde_json::from_str(text).map_err(|err| Error::InvalidBody(err.to_string())),
+        BodyFormat::Form => serde_qs::Config::new()
+            .use_form_encoding(true)
+            .deserialize_str(text)
+            .map_err(|err| Error::InvalidBody(err.to_string())),
+    }?;
+
+    Ok((parsed, body))
+}
+
+// For the few endpoints whose body is a bare value rather than JSON, whatever
+// its content type.
+pub async fn read_text_body(req: &mut Request, max_body_bytes: usize) -> Result<String, Error> {
+    let body = read_decoded_body(req, max_body_bytes).await?;
+    String::from_utf8(body).map_err(|_err| Error::BadRequest)
+}
+
+fn media_type(req: &Request) -> Result<String, Error> {
+    let content_type = req
+        .headers()
+        .get("Content-Type")
+        .map_err(|_err| Error::BadRequest)?
+        .unwrap_or_default();
+
+    Ok(content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase())
+}
+
+async fn read_decoded_body(req: &mut Request, max_body_bytes: usize) -> Result<Vec<u8>, Error> {
+    let content_encoding = req
+        .headers()
+        .get("Content-Encoding")
+        .map_err(|_err| Error::BadRequest)?
+        .unwrap_or_default();
+
+    let body = read_body(req, max_body_bytes).await?;
+    decompress(body, &content_encoding, max_body_bytes)
+}
+
+// The limit applies to the decompressed size as well, so a small compressed
+// body can't expand past `max_body_bytes`.
+fn decompress(body: Vec<u8>, content_encoding: &str, max_body_bytes: usize) -> Result<Vec<u8>, Error> {
+    let decoder: Box<dyn Read + '_> = match content_encoding.trim().to_ascii_lowercase().as_str() {
+        "" | "identity" => return Ok(body),
+        "gzip" | "x-gzip" => Box::new(GzDecoder::new(body.as_slice())),
+        // HTTP's `deflate` is the zlib format, not raw deflate
+        "deflate" => Box::new(ZlibDecoder::new(body.as_slice())),
+        _ => return Err(Error::UnsupportedMediaType),
+    };
+
+    let mut decompressed = Vec::new();
+    decoder
+        .take(max_body_bytes as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|_err| Error::BadRequest)?;
+    if decompressed.len() > max_body_bytes {
+        return Err(Error::PayloadTooLarge);
+    }
+
+    Ok(decompressed)
+}
+
+// A declared `Content-Length` over the limit is rejected before reading
+// anything; chunked bodies are counted as they stream in, so neither is ever
+// buffered past `max_body_bytes`.
+async fn read_body(req: &mut Request, max_body_bytes: usize) -> Result<Vec<u8>, Error> {
+    let content_length = req
+        .headers()
+        .get("Content-Length")
+        .map_err(|_err| Error::BadRequest)?
+        .and_then(|length| length.parse::<usize>().ok());
+    if content_length.is_some_and(|length| length > max_body_bytes) {
+        return Err(Error::PayloadTooLarge);
+    }
+
+    // Only a request without a body has no stream.
+    let Ok(mut stream) = req.stream() else {
+        return Ok(Vec::new());
+    };
+
+    let mut body = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|_err| Error::BadRequest)?;
+        if body.len() + chunk.len() > max_body_bytes {
+            return Err(Error::PayloadTooLarge);
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(body)
+}
@@ -0,0 +1,30 @@
+//! A per-isolate cap on concurrent password hashes. Each argon2 hash holds its
+//! memory until it finishes, so a burst landing on one isolate could otherwise
+//! exhaust it; other isolates keep their own count.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::error::Error;
+
+// Password hashes and verifications this isolate is running right now.
+static HASHES: AtomicU32 = AtomicU32::new(0);
+
+// Gives its slot back on drop, however the handler returns.
+pub struct HashPermit;
+
+impl Drop for HashPermit {
+    fn drop(&mut self) {
+        HASHES.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// Fails rather than waits: a request queued behind others would only hold its
+// body in memory for longer.
+pub fn acquire_hash_permit(max_concurrent_hashes: u32) -> Result<HashPermit, Error> {
+    if HASHES.fetch_add(1, Ordering::Relaxed) >= max_concurrent_hashes {
+        HASHES.fetch_sub(1, Ordering::Relaxed);
+        return Err(Error::ServiceBusy);
+    }
+
+    Ok(HashPermit)
+}
@@ -0,0 +1,347 @@
+//! Configuration read from the worker's variables and secrets.
+
+use std::collections::BTreeMap;
+
+use argon2::Params;
+use worker::Env;
+
+use crate::bcrypt_handler::BCRYPT_WORK_FACTORS;
+use crate::error::{Error, ErrorFormat};
+use crate::hash_handler::HASH_ALGORITHMS;
+use crate::hmac_handler::{hmac_compute, HmacAlgorithm};
+use crate::routing::ROUTES;
+
+const DEFAULT_MAX_PASSWORD_BYTES: usize = 1024;
+const DEFAULT_MAX_BODY_BYTES: usize = 64 * 1024;
+const DEFAULT_ARGON2_MAX_MEMORY_KIB: u32 = 64 * 1024;
+const DEFAULT_ARGON2_MAX_TIME_COST: u32 = 10;
+const DEFAULT_ARGON2_MAX_PARALLELISM: u32 = 8;
+const DEFAULT_SCRYPT_MAX_MEMORY_KIB: u64 = 64 * 1024;
+const DEFAULT_SCRYPT_MAX_P: u32 = 16;
+const DEFAULT_BALLOON_MAX_SPACE_COST: u32 = 1 << 20;
+const DEFAULT_BALLOON_MAX_TIME_COST: u32 = 16;
+const DEFAULT_PBKDF2_MAX_ROUNDS: u32 = 1_000_000;
+const DEFAULT_BCRYPT_MAX_WORK_FACTOR: u32 = 14;
+const DEFAULT_HASH_ALGORITHM: &str = "argon2id";
+const DEFAULT_NONCE_TTL_SECONDS: u64 = 60 * 60;
+const DEFAULT_READONLY_CACHE_SECONDS: u64 = 60;
+const DEFAULT_MAX_CONCURRENT_HASHES: u32 = 4;
+const DEFAULT_RATE_LIMIT_PER_MINUTE: u64 = 60;
+
+pub struct Config {
+    pub defaults: Defaults,
+    pub argon2_limits: Argon2Limits,
+    pub scrypt_limits: ScryptLimits,
+    pub balloon_limits: BalloonLimits,
+    pub pbkdf2_max_rounds: u32,
+    pub bcrypt_max_work_factor: u32,
+    pub peppers: Peppers,
+    pub argon2_secret: Argon2Secret,
+    pub max_password_bytes: usize,
+    pub max_body_bytes: usize,
+    pub max_concurrent_hashes: u32,
+    pub rate_limit_per_minute: u64,
+    pub cors_allow_origin: Option<String>,
+    pub jwt_secret: Option<String>,
+    pub jwt_leeway_seconds: u64,
+    pub ed25519_private_key: Option<String>,
+    pub nonce_secret: Option<String>,
+    pub nonce_ttl_seconds: u64,
+    pub server_timing: bool,
+    pub readonly_cache_seconds: u64,
+    pub route_prefix: Option<String>,
+    pub default_hash_algorithm: String,
+    pub error_format: ErrorFormat,
+    pub enabled_algorithms: Option<Vec<String>>,
+    pub legacy_field_names: bool,
+}
+
+// Parameters used when a hash request omits `options`.
+pub struct Defaults {
+    pub argon2: Params,
+    pub bcrypt_work_factor: u32,
+}
+
+// What requested argon2 options may ask for; costs past the isolate's memory
+// crash it instead of failing cleanly.
+pub struct Argon2Limits {
+    pub max_memory_kib: u32,
+    pub max_time_cost: u32,
+    pub max_parallelism: u32,
+    pub allow_custom_salt: bool,
+}
+
+// The same for scrypt, whose memory is 128 * r * 2^log_n bytes. There is no
+// fallible allocation to fall back on as for argon2, so the budget is all that
+// keeps a hash from aborting the isolate. These apply to the costs stored
+// hashes ask for too.
+pub struct ScryptLimits {
+    pub max_memory_kib: u64,
+    pub max_p: u32,
+}
+
+// And for balloon hashing, whose blocks are one digest wide, so the default
+// space cost is 32 MiB with SHA-256 and 64 MiB with SHA-512. Each round passes
+// over every block, so time scales with both costs.
+pub struct BalloonLimits {
+    pub max_space_cost: u32,
+    pub max_time_cost: u32,
+}
+
+// `ARGON2_SECRET` and the `keyid` hashes keyed with it carry: `ARGON2_SECRET_ID`,
+// or else an HMAC of a fixed label under the secret, so the keyid doesn't reveal
+// it. Only `ARGON2_SECRET_ID` lets verify recognise keyed hashes while the secret
+// itself is missing.
+#[derive(Default)]
+pub struct Argon2Secret {
+    pub key: Option<String>,
+    pub keyid: Option<Vec<u8>>,
+}
+
+// The unversioned `HASH_PEPPER` and the rotatable `PEPPER_V<n>` secrets; see
+// `password::Pepper` for how a hash records which one it was made with.
+pub struct Peppers {
+    pub unversioned: Option<String>,
+    pub versioned: BTreeMap<u32, String>,
+    pub current_version: Option<u32>,
+}
+
+impl Config {
+    pub fn from_env(env: &Env) -> Result<Self, Error> {
+        let defaults = Defaults::from_env(env)?;
+        let argon2_limits = Argon2Limits::from_env(env)?;
+        let scrypt_limits = ScryptLimits::from_env(env)?;
+        let balloon_limits = BalloonLimits::from_env(env)?;
+        let pbkdf2_max_rounds = parse_var(env, "PBKDF2_MAX_ROUNDS")?.unwrap_or(DEFAULT_PBKDF2_MAX_ROUNDS);
+        let bcrypt_max_work_factor = parse_var(env, "BCRYPT_MAX_WORK_FACTOR")?.unwrap_or(DEFAULT_BCRYPT_MAX_WORK_FACTOR);
+        if !BCRYPT_WORK_FACTORS.contains(&bcrypt_max_work_factor) {
+            return Err(Error::InvalidConfiguration(format!(
+                "BCRYPT_MAX_WORK_FACTOR must be between {} and {}",
+                BCRYPT_WORK_FACTORS.start(),
+                BCRYPT_WORK_FACTORS.end()
+            )));
+        }
+        if defaults.bcrypt_work_factor > bcrypt_max_work_factor {
+            return Err(Error::InvalidConfiguration(
+                "BCRYPT_WORK_FACTOR must not exceed BCRYPT_MAX_WORK_FACTOR".into(),
+            ));
+        }
+
+        let peppers = Peppers::from_env(env)?;
+        let argon2_secret = Argon2Secret::from_env(env)?;
+
+        let max_password_bytes = parse_var(env, "MAX_PASSWORD_BYTES")?
+            .unwrap_or(DEFAULT_MAX_PASSWORD_BYTES);
+        let max_body_bytes = parse_var(env, "MAX_BODY_BYTES")?
+            .unwrap_or(DEFAULT_MAX_BODY_BYTES);
+        let max_concurrent_hashes = parse_var(env, "MAX_CONCURRENT_HASHES")?
+            .unwrap_or(DEFAULT_MAX_CONCURRENT_HASHES);
+        if max_concurrent_hashes == 0 {
+            return Err(Error::InvalidConfiguration("MAX_CONCURRENT_HASHES must be at least 1".into()));
+        }
+        let rate_limit_per_minute = parse_var(env, "RATE_LIMIT_PER_MINUTE")?
+            .unwrap_or(DEFAULT_RATE_LIMIT_PER_MINUTE);
+
+        let cors_allow_origin = env.var("CORS_ALLOW_ORIGIN").ok().map(|var| var.to_string());
+        let jwt_secret = env.secret("JWT_SECRET").ok().map(|secret| secret.to_string());
+
+        let jwt_leeway_seconds = parse_var(env, "JWT_LEEWAY_SECONDS")?.unwrap_or(0);
+        let ed25519_private_key = env.secret("ED25519_PRIVATE_KEY").ok().map(|secret| secret.to_string());
+        let nonce_secret = env.secret("NONCE_SECRET").ok().map(|secret| secret.to_string());
+        let nonce_ttl_seconds = parse_var(env, "NONCE_TTL_SECONDS")?.unwrap_or(DEFAULT_NONCE_TTL_SECONDS);
+        let server_timing = !env.var("DISABLE_SERVER_TIMING").is_ok_and(|var| var.to_string() == "true");
+        let readonly_cache_seconds = parse_var(env, "READONLY_CACHE_SECONDS")?.unwrap_or(DEFAULT_READONLY_CACHE_SECONDS);
+        let route_prefix = env
+            .var("ROUTE_PREFIX")
+            .ok()
+            .map(|var| var.to_string().trim_matches('/').to_string())
+            .filter(|prefix| !prefix.is_empty())
+            .map(|prefix| format!("/{prefix}"));
+        let default_hash_algorithm = env
+            .var("DEFAULT_HASH_ALGORITHM")
+            .map(|var| var.to_string())
+            .unwrap_or_else(|_err| DEFAULT_HASH_ALGORITHM.to_string());
+        if !HASH_ALGORITHMS.contains(&default_hash_algorithm.as_str()) {
+            return Err(Error::InvalidConfiguration(format!(
+                "DEFAULT_HASH_ALGORITHM must be one of {}",
+                HASH_ALGORITHMS.join(", ")
+            )));
+        }
+
+        let enabled_algorithms = env
+            .var("ENABLED_ALGORITHMS")
+            .ok()
+            .map(|var| parse_enabled_algorithms(&var.to_string()))
+            .transpose()?;
+
+        Ok(Config {
+            defaults,
+            argon2_limits,
+            scrypt_limits,
+            balloon_limits,
+            pbkdf2_max_rounds,
+            bcrypt_max_work_factor,
+            peppers,
+            argon2_secret,
+            max_password_bytes,
+            max_body_bytes,
+            max_concurrent_hashes,
+            rate_limit_per_minute,
+            cors_allow_origin,
+            jwt_secret,
+            jwt_leeway_seconds,
+            ed25519_private_key,
+            nonce_secret,
+            nonce_ttl_seconds,
+            server_timing,
+            readonly_cache_seconds,
+            route_prefix,
+            default_hash_algorithm,
+            error_format: ErrorFormat::from_env(env),
+            enabled_algorithms,
+            legacy_field_names: env.var("LEGACY_FIELD_NAMES").is_ok_and(|var| var.to_string() == "true"),
+        })
+    }
+
+    pub fn argon2_secret(&self) -> &Argon2Secret {
+        &self.argon2_secret
+    }
+
+    // Names as the routes label them, e.g. `argon2` or `bcrypt-sha256`.
+    pub fn algorithm_enabled(&self, algorithm: &str) -> bool {
+        self.enabled_algorithms
+            .as_ref()
+            .is_none_or(|enabled| enabled.iter().any(|name| name == algorithm))
+    }
+}
+
+impl Argon2Limits {
+    fn from_env(env: &Env) -> Result<Self, Error> {
+        Ok(Argon2Limits {
+            max_memory_kib: parse_var(env, "ARGON2_MAX_MEMORY_KIB")?.unwrap_or(DEFAULT_ARGON2_MAX_MEMORY_KIB),
+            max_time_cost: parse_var(env, "ARGON2_MAX_TIME_COST")?.unwrap_or(DEFAULT_ARGON2_MAX_TIME_COST),
+            max_parallelism: parse_var(env, "ARGON2_MAX_PARALLELISM")?.unwrap_or(DEFAULT_ARGON2_MAX_PARALLELISM),
+            allow_custom_salt: env.var("ALLOW_CUSTOM_SALT").is_ok_and(|var| var.to_string() == "true"),
+        })
+    }
+}
+
+impl Argon2Secret {
+    fn from_env(env: &Env) -> Result<Self, Error> {
+        let key = env.secret("ARGON2_SECRET").ok().map(|secret| secret.to_string());
+        let keyid = match env.var("ARGON2_SECRET_ID") {
+            Ok(var) => {
+                let keyid = var.to_string().into_bytes();
+                if keyid.is_empty() || keyid.len() > Params::MAX_KEYID_LEN {
+                    return Err(Error::InvalidConfiguration(format!(
+                        "ARGON2_SECRET_ID must be between 1 and {} bytes",
+                        Params::MAX_KEYID_LEN
+                    )));
+                }
+                Some(keyid)
+            }
+            Err(_err) => key.as_deref().map(|key| {
+                hmac_compute(HmacAlgorithm::Sha256, key.as_bytes(), b"cryptoflare-argon2-keyid")[..Params::MAX_KEYID_LEN]
+                    .to_vec()
+            }),
+        };
+
+        Ok(Argon2Secret { key, keyid })
+    }
+
+    pub fn key(&self) -> Option<&[u8]> {
+        self.key.as_deref().map(str::as_bytes)
+    }
+}
+
+impl ScryptLimits {
+    fn from_env(env: &Env) -> Result<Self, Error> {
+        Ok(ScryptLimits {
+            max_memory_kib: parse_var(env, "SCRYPT_MAX_MEMORY_KIB")?.unwrap_or(DEFAULT_SCRYPT_MAX_MEMORY_KIB),
+            max_p: parse_var(env, "SCRYPT_MAX_P")?.unwrap_or(DEFAULT_SCRYPT_MAX_P),
+        })
+    }
+}
+
+impl BalloonLimits {
+    fn from_env(env: &Env) -> Result<Self, Error> {
+        Ok(BalloonLimits {
+            max_space_cost: parse_var(env, "BALLOON_MAX_SPACE_COST")?.unwrap_or(DEFAULT_BALLOON_MAX_SPACE_COST),
+            max_time_cost: parse_var(env, "BALLOON_MAX_TIME_COST")?.unwrap_or(DEFAULT_BALLOON_MAX_TIME_COST),
+        })
+    }
+}
+
+impl Peppers {
+    // Secrets can't be listed, so versions past the current one are never
+    // looked up; lowering `CURRENT_PEPPER_VERSION` strands hashes made since.
+    fn from_env(env: &Env) -> Result<Self, Error> {
+        let unversioned = env.secret("HASH_PEPPER").ok().map(|secret| secret.to_string());
+        let current_version: Option<u32> = parse_var(env, "CURRENT_PEPPER_VERSION")?;
+
+        let versioned = (1..=current_version.unwrap_or(0))
+            .filter_map(|version| {
+                let secret = env.secret(&format!("PEPPER_V{version}")).ok()?;
+                Some((version, secret.to_string()))
+            })
+            .collect();
+
+        Ok(Peppers {
+            unversioned,
+            versioned,
+            current_version,
+        })
+    }
+}
+
+impl Defaults {
+    fn from_env(env: &Env) -> Result<Self, Error> {
+        let argon2_default = Params::default();
+        let memory_cost = parse_var(env, "ARGON2_MEMORY_COST")?.unwrap_or(argon2_default.m_cost());
+        let time_cost = parse_var(env, "ARGON2_TIME_COST")?.unwrap_or(argon2_default.t_cost());
+        let parallelism = parse_var(env, "ARGON2_PARALLELISM")?.unwrap_or(argon2_default.p_cost());
+
+        let argon2 = Params::new(memory_cost, time_cost, parallelism, None)
+            .map_err(|err| Error::InvalidConfiguration(format!("argon2 defaults: {err}")))?;
+
+        let bcrypt_work_factor = parse_var(env, "BCRYPT_WORK_FACTOR")?.unwrap_or(bcrypt::DEFAULT_COST);
+        if !BCRYPT_WORK_FACTORS.contains(&bcrypt_work_factor) {
+            return Err(Error::InvalidConfiguration(format!(
+                "BCRYPT_WORK_FACTOR must be between {} and {}",
+                BCRYPT_WORK_FACTORS.start(),
+                BCRYPT_WORK_FACTORS.end()
+            )));
+        }
+
+        Ok(Defaults {
+            argon2,
+            bcrypt_work_factor,
+        })
+    }
+}
+
+fn parse_enabled_algorithms(var: &str) -> Result<Vec<String>, Error> {
+    let known: Vec<&str> = ROUTES.iter().filter_map(|entry| entry.algorithm).collect();
+    var.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            if known.contains(&name) {
+                Ok(name.to_string())
+            } else {
+                Err(Error::InvalidConfiguration(format!("ENABLED_ALGORITHMS: unknown algorithm {name}")))
+            }
+        })
+        .collect()
+}
+
+pub fn parse_var<T: std::str::FromStr>(env: &Env, name: &str) -> Result<Option<T>, Error> {
+    match env.var(name) {
+        Ok(var) => var
+            .to_string()
+            .parse()
+            .map(Some)
+            .map_err(|_err| Error::InvalidConfiguration(format!("{name} is not a valid number"))),
+        Err(_err) => Ok(None),
+    }
+}
@@ -0,0 +1,23 @@
+//! CORS preflight responses, enabled by `CORS_ALLOW_ORIGIN`.
+
+use worker::{Headers, Response};
+
+// Response headers other than the CORS-safelisted ones that scripts on the
+// allowed origin may read; `Retry-After` comes with rate limiting.
+const EXPOSE_HEADERS: &str = "X-Request-Id, Server-Timing, Retry-After";
+
+pub fn cors_preflight_response() -> worker::Result<Response> {
+    let mut headers = Headers::new();
+    headers.set("Access-Control-Allow-Methods", "GET, POST, OPTIONS")?;
+    headers.set(
+        "Access-Control-Allow-Headers",
+        "Content-Type, Authorization, Idempotency-Key, Content-Encoding",
+    )?;
+
+    Ok(Response::empty()?.with_status(204).with_headers(headers))
+}
+
+pub fn set_cors_headers(headers: &mut Headers, origin: &str) -> worker::Result<()> {
+    headers.set("Access-Control-Allow-Origin", origin)?;
+    headers.set("Access-Control-Expose-Headers", EXPOSE_HEADERS)
+}
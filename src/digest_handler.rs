@@ -0,0 +1,131 @@
+//! SHA-2 and BLAKE3 digests.
+
+use futures_util::StreamExt;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use worker::Request;
+
+use crate::body::parse_body;
+use crate::config::Config;
+use crate::encoding::{Encoding, decode, encode};
+use crate::error::Error;
+
+// ### Types
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct DigestRequest {
+    pub algorithm: String,
+    pub data: String,
+    #[serde(default)]
+    pub input_encoding: Encoding,
+    pub output_encoding: Option<Encoding>,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct DigestResponse {
+    pub digest: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct DigestStreamQuery {
+    pub encoding: Option<Encoding>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct Blake3Request {
+    pub data: String,
+    pub key: Option<String>,
+    #[serde(default)]
+    pub input_encoding: Encoding,
+    pub output_encoding: Option<Encoding>,
+    pub length: Option<usize>,
+}
+
+const DEFAULT_BLAKE3_LENGTH: usize = blake3::OUT_LEN;
+const MAX_BLAKE3_LENGTH: usize = 1024;
+
+// ### Functions
+pub async fn digest_handler(mut req: Request, config: &Config) -> Result<String, Error> {
+    let digest_req: DigestRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let data = decode(&digest_req.data, digest_req.input_encoding)?;
+    let digest = digest(&digest_req.algorithm, &data)?;
+
+    let digest_response = DigestResponse {
+        digest: encode(&digest, digest_req.output_encoding.unwrap_or(Encoding::Hex))?,
+    };
+    serde_json::to_string(&digest_response).map_err(|_err| Error::InternalServerError)
+}
+
+fn digest(algorithm: &str, data: &[u8]) -> Result<Vec<u8>, Error> {
+    match algorithm {
+        "sha256" => Ok(Sha256::digest(data).to_vec()),
+        "sha384" => Ok(Sha384::digest(data).to_vec()),
+        "sha512" => Ok(Sha512::digest(data).to_vec()),
+        _ => Err(Error::UnsupportedAlgorithm),
+    }
+}
+
+// Hashes the raw request body chunk by chunk, so memory stays bounded by the
+// chunk size rather than the upload size.
+pub async fn digest_stream_handler(mut req: Request) -> Result<String, Error> {
+    let query: DigestStreamQuery = req
+        .query()
+        .map_err(|_err| Error::BadRequest)?;
+
+    let mut stream = req.stream().map_err(|_err| Error::BadRequest)?;
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|_err| Error::BadRequest)?;
+        hasher.update(&chunk);
+    }
+
+    let digest_response = DigestResponse {
+        digest: encode(&hasher.finalize(), query.encoding.unwrap_or(Encoding::Hex))?,
+    };
+    serde_json::to_string(&digest_response).map_err(|_err| Error::InternalServerError)
+}
+
+pub async fn blake3_handler(mut req: Request, config: &Config) -> Result<String, Error> {
+    let blake3_req: Blake3Request = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let data = decode(&blake3_req.data, blake3_req.input_encoding)?;
+    let key = blake3_req
+        .key
+        .map(|key| decode(&key, blake3_req.input_encoding))
+        .transpose()?;
+
+    let digest = blake3_digest(
+        &data,
+        key.as_deref(),
+        blake3_req.length.unwrap_or(DEFAULT_BLAKE3_LENGTH),
+    )?;
+
+    let digest_response = DigestResponse {
+        digest: encode(&digest, blake3_req.output_encoding.unwrap_or(Encoding::Hex))?,
+    };
+    serde_json::to_string(&digest_response).map_err(|_err| Error::InternalServerError)
+}
+
+// `length` past 32 bytes reads further from the BLAKE3 XOF.
+fn blake3_digest(data: &[u8], key: Option<&[u8]>, length: usize) -> Result<Vec<u8>, Error> {
+    if !(1..=MAX_BLAKE3_LENGTH).contains(&length) {
+        return Err(Error::InvalidHashOptions(format!(
+            "length must be between 1 and {MAX_BLAKE3_LENGTH}"
+        )));
+    }
+
+    let mut hasher = match key {
+        Some(key) => {
+            let key: &[u8; blake3::KEY_LEN] = key
+                .try_into()
+                .map_err(|_err| Error::InvalidKeyLength)?;
+            blake3::Hasher::new_keyed(key)
+        }
+
+        None => blake3::Hasher::new(),
+    };
+    hasher.update(data);
+
+    let mut digest = vec![0u8; length];
+    hasher.finalize_xof().fill(&mut digest);
+    Ok(digest)
+}
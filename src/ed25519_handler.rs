@@ -0,0 +1,85 @@
+//! Ed25519 signing with the configured key, and verification against any public key.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use worker::Request;
+
+use crate::body::parse_body;
+use crate::config::Config;
+use crate::encoding::{Encoding, decode, encode};
+use crate::error::Error;
+
+// ### Types
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct Ed25519SignRequest {
+    pub message: String,
+    /// How `message` is encoded, defaults to `utf8`.
+    #[serde(default)]
+    pub encoding: Encoding,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct Ed25519SignResponse {
+    /// base64
+    pub signature: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct Ed25519VerifyRequest {
+    pub message: String,
+    /// base64
+    pub signature: String,
+    /// base64, the 32 byte public key
+    pub public_key: String,
+    #[serde(default)]
+    pub encoding: Encoding,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct Ed25519VerifyResponse {
+    pub valid: bool,
+}
+
+// ### Functions
+pub async fn ed25519_sign_handler(mut req: Request, config: &Config) -> Result<String, Error> {
+    let sign_req: Ed25519SignRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let private_key = config.ed25519_private_key.as_deref().ok_or(Error::SigningKeyMissing)?;
+    let signing_key = ed25519_signing_key(private_key)?;
+    let message = decode(&sign_req.message, sign_req.encoding)?;
+
+    let signature = signing_key.sign(&message);
+
+    let sign_response = Ed25519SignResponse {
+        signature: encode(&signature.to_bytes(), Encoding::Base64)?,
+    };
+    serde_json::to_string(&sign_response).map_err(|_err| Error::InternalServerError)
+}
+
+// `ED25519_PRIVATE_KEY` is the base64 32 byte seed, as in RFC 8032.
+fn ed25519_signing_key(private_key: &str) -> Result<SigningKey, Error> {
+    let seed = decode(private_key, Encoding::Base64).map_err(|_err| Error::InvalidKeyLength)?;
+    let seed = seed.try_into().map_err(|_err| Error::InvalidKeyLength)?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+pub async fn ed25519_verify_handler(mut req: Request, config: &Config) -> Result<String, Error> {
+    let verify_req: Ed25519VerifyRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let public_key = decode(&verify_req.public_key, Encoding::Base64)?;
+    let public_key = public_key
+        .try_into()
+        .map_err(|_err| Error::InvalidKeyLength)
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).map_err(|_err| Error::InvalidKeyLength))?;
+    let signature = decode(&verify_req.signature, Encoding::Base64)?;
+    let message = decode(&verify_req.message, verify_req.encoding)?;
+
+    let valid = ed25519_verify(&public_key, &message, &signature);
+    let verify_response = Ed25519VerifyResponse { valid };
+    serde_json::to_string(&verify_response).map_err(|_err| Error::InternalServerError)
+}
+
+// A signature of the wrong length is as invalid as a wrong one. Strict
+// verification also rejects malleable signatures and small-order keys.
+fn ed25519_verify(public_key: &VerifyingKey, message: &[u8], signature: &[u8]) -> bool {
+    Signature::from_slice(signature).is_ok_and(|signature| public_key.verify_strict(message, &signature).is_ok())
+}
@@ -0,0 +1,149 @@
+//! Conversion between utf8, hex, base64, base64url and base32.
+
+use base64::{engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD as BASE64URL}, Engine};
+use worker::Request;
+
+use crate::body::parse_body;
+use crate::config::Config;
+use crate::error::Error;
+
+// ### Types
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct ConvertRequest {
+    pub data: String,
+    pub from: Option<Encoding>,
+    pub to: Option<Encoding>,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct ConvertResponse {
+    pub result: String,
+}
+
+#[derive(serde::Deserialize, Default, Clone, Copy, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Hex,
+    Base64,
+    Base64url,
+    Base32,
+}
+
+const BASE32: base32::Alphabet = base32::Alphabet::Rfc4648 { padding: false };
+
+// ### Functions
+// `/encode` and `/decode` only differ in their defaults for `from` and `to`.
+pub async fn convert_handler(mut req: Request, config: &Config, default_from: Encoding, default_to: Encoding) -> Result<String, Error> {
+    let convert_req: ConvertRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let convert_response = convert(&convert_req, default_from, default_to)?;
+    serde_json::to_string(&convert_response).map_err(|_err| Error::InternalServerError)
+}
+
+fn convert(convert_req: &ConvertRequest, default_from: Encoding, default_to: Encoding) -> Result<ConvertResponse, Error> {
+    let bytes = decode(&convert_req.data, convert_req.from.unwrap_or(default_from))?;
+
+    Ok(ConvertResponse {
+        result: encode(&bytes, convert_req.to.unwrap_or(default_to))?,
+    })
+}
+
+pub fn decode(data: &str, encoding: Encoding) -> Result<Vec<u8>, Error> {
+    match encoding {
+        Encoding::Utf8 => Ok(data.as_bytes().to_vec()),
+        Encoding::Hex => hex::decode(data).map_err(|_err| Error::InvalidEncoding),
+        Encoding::Base64 => BASE64.decode(data).map_err(|_err| Error::InvalidEncoding),
+        Encoding::Base64url => BASE64URL.decode(data).map_err(|_err| Error::InvalidEncoding),
+        Encoding::Base32 => decode_base32(data),
+    }
+}
+
+pub fn encode(bytes: &[u8], encoding: Encoding) -> Result<String, Error> {
+    match encoding {
+        Encoding::Utf8 => String::from_utf8(bytes.to_vec()).map_err(|_err| Error::InvalidEncoding),
+        Encoding::Hex => Ok(hex::encode(bytes)),
+        Encoding::Base64 => Ok(BASE64.encode(bytes)),
+        Encoding::Base64url => Ok(BASE64URL.encode(bytes)),
+        Encoding::Base32 => Ok(base32::encode(BASE32, bytes)),
+    }
+}
+
+// Case-insensitive, and padding is accepted though never emitted.
+fn decode_base32(data: &str) -> Result<Vec<u8>, Error> {
+    let data = data.trim_end_matches('=').to_ascii_uppercase();
+    base32::decode(BASE32, &data).ok_or(Error::InvalidEncoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base32_round_trips() {
+        // RFC 4648's test vectors, without their padding.
+        for (bytes, encoded) in [("", ""), ("f", "MY"), ("fo", "MZXQ"), ("foob", "MZXW6YQ"), ("foobar", "MZXW6YTBOI")] {
+            assert_eq!(encode(bytes.as_bytes(), Encoding::Base32).ok().unwrap(), encoded);
+            assert_eq!(decode(encoded, Encoding::Base32).ok().unwrap(), bytes.as_bytes());
+        }
+    }
+
+    #[test]
+    fn base32_decodes_lowercase_and_padded() {
+        assert_eq!(decode("mzxw6ytboi", Encoding::Base32).ok().unwrap(), b"foobar");
+        assert_eq!(decode("MZXW6YQ=", Encoding::Base32).ok().unwrap(), b"foob");
+    }
+
+    #[test]
+    fn invalid_base32_is_rejected() {
+        for data in ["MZXW6YTB0I", "MZXW-YTBOI", "MZXW6YTB1I"] {
+            let err = decode(data, Encoding::Base32).err().unwrap();
+            assert_eq!(err.code(), "INVALID_ENCODING");
+        }
+    }
+
+    // As `/encode` and `/decode` would answer, with the body as `parse_body` parses it.
+    fn convert_json(json: serde_json::Value, default_from: Encoding, default_to: Encoding) -> Result<String, Error> {
+        let convert_req: ConvertRequest =
+            serde_json::from_value(json).map_err(|err| Error::InvalidBody(err.to_string()))?;
+        convert(&convert_req, default_from, default_to).map(|convert_response| convert_response.result)
+    }
+
+    fn encode_json(json: serde_json::Value) -> Result<String, Error> {
+        convert_json(json, Encoding::Utf8, Encoding::Base64)
+    }
+
+    fn decode_json(json: serde_json::Value) -> Result<String, Error> {
+        convert_json(json, Encoding::Base64, Encoding::Utf8)
+    }
+
+    #[test]
+    fn encode_and_decode_convert_to_and_from_base32() {
+        let encoded = encode_json(serde_json::json!({ "data": "foobar", "to": "base32" }));
+        assert_eq!(encoded.ok().unwrap(), "MZXW6YTBOI");
+        let decoded = decode_json(serde_json::json!({ "data": "mzxw6ytboi", "from": "base32" }));
+        assert_eq!(decoded.ok().unwrap(), "foobar");
+        let hex = encode_json(serde_json::json!({ "data": "MZXW6", "from": "base32", "to": "hex" }));
+        assert_eq!(hex.ok().unwrap(), "666f6f");
+    }
+
+    #[test]
+    fn convert_rejects_unknown_encodings_and_bad_data() {
+        for json in [
+            serde_json::json!({ "data": "foobar", "to": "base58" }),
+            serde_json::json!({ "data": "foobar", "from": "Base32" }),
+        ] {
+            let err = encode_json(json).err().unwrap();
+            assert_eq!(err.code(), "BAD_REQUEST");
+        }
+
+        for json in [
+            serde_json::json!({ "data": "MZXW6YTB0I", "from": "base32" }),
+            serde_json::json!({ "data": "_w", "from": "base64url" }), // 0xff, not utf8
+        ] {
+            let err = decode_json(json).err().unwrap();
+            assert_eq!(err.code(), "INVALID_ENCODING");
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! AES-256-GCM, ChaCha20-Poly1305 and XChaCha20-Poly1305 encryption and decryption.
+
+use aes_gcm::{
+    aead::{self, generic_array::{typenum::Unsigned, GenericArray}, Aead, AeadCore, KeyInit, Payload},
+    Aes256Gcm,
+};
+use chacha20poly1305::{ChaCha20Poly1305, XChaCha20Poly1305};
+use worker::Request;
+
+use crate::body::parse_body;
+use crate::config::Config;
+use crate::encoding::{Encoding, decode, encode};
+use crate::error::Error;
+use crate::random_handler::fill_random;
+
+// ### Types
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct EncryptRequest {
+    pub plaintext: String,
+    pub key: String,
+    pub aad: Option<String>,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct EncryptResponse {
+    pub ciphertext: String,
+    pub nonce: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct DecryptRequest {
+    pub ciphertext: String,
+    pub nonce: String,
+    pub key: String,
+    pub aad: Option<String>,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct DecryptResponse {
+    pub plaintext: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct ReencryptRequest {
+    pub ciphertext: String,
+    pub nonce: String,
+    pub old_key: String,
+    pub new_key: String,
+    /// Authenticated under both keys, so it carries over unchanged.
+    pub aad: Option<String>,
+}
+
+// All three take 32 byte keys; they differ in nonce size (12 bytes, or 24 for
+// XChaCha20-Poly1305) and in which hardware they are fast on.
+#[derive(Clone, Copy)]
+pub enum Cipher {
+    Aes256Gcm,
+    ChaCha20Poly1305,
+    XChaCha20Poly1305,
+}
+
+// ### Functions
+pub async fn encrypt_handler(mut req: Request, config: &Config, cipher: Cipher) -> Result<String, Error> {
+    let encrypt_req: EncryptRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let plaintext = decode(&encrypt_req.plaintext, Encoding::Base64)?;
+    let key = decode(&encrypt_req.key, Encoding::Base64)?;
+    let aad = decode_aad(encrypt_req.aad.as_deref())?;
+
+    let (nonce, ciphertext) = encrypt(cipher, &key, &plaintext, &aad)?;
+
+    let encrypt_response = EncryptResponse {
+        ciphertext: encode(&ciphertext, Encoding::Base64)?,
+        nonce: encode(&nonce, Encoding::Base64)?,
+    };
+    serde_json::to_string(&encrypt_response).map_err(|_err| Error::InternalServerError)
+}
+
+fn decode_aad(aad: Option<&str>) -> Result<Vec<u8>, Error> {
+    match aad {
+        Some(aad) => decode(aad, Encoding::Base64),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub fn encrypt(cipher: Cipher, key: &[u8], plaintext: &[u8], aad: &[u8]) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    match cipher {
+        Cipher::Aes256Gcm => aead_encrypt::<Aes256Gcm>(key, plaintext, aad),
+        Cipher::ChaCha20Poly1305 => aead_encrypt::<ChaCha20Poly1305>(key, plaintext, aad),
+        Cipher::XChaCha20Poly1305 => aead_encrypt::<XChaCha20Poly1305>(key, plaintext, aad),
+    }
+}
+
+// A fresh random nonce per message. 96 bit nonces stay safe for far more
+// messages than any one key should see; 192 bit ones remove the limit.
+fn aead_encrypt<C: Aead + AeadCore + KeyInit>(key: &[u8], plaintext: &[u8], aad: &[u8]) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let cipher = C::new_from_slice(key).map_err(|_err| Error::InvalidKeyLength)?;
+    let mut nonce = aead::Nonce::<C>::default();
+    fill_random(&mut nonce)?;
+
+    let ciphertext = cipher
+        .encrypt(&nonce, Payload { msg: plaintext, aad })
+        .map_err(|_err| Error::InternalServerError)?;
+
+    Ok((nonce.to_vec(), ciphertext))
+}
+
+pub async fn decrypt_handler(mut req: Request, config: &Config, cipher: Cipher) -> Result<String, Error> {
+    let decrypt_req: DecryptRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let ciphertext = decode(&decrypt_req.ciphertext, Encoding::Base64)?;
+    let nonce = decode(&decrypt_req.nonce, Encoding::Base64)?;
+    let key = decode(&decrypt_req.key, Encoding::Base64)?;
+    let aad = decode_aad(decrypt_req.aad.as_deref())?;
+
+    let plaintext = decrypt(cipher, &key, &nonce, &ciphertext, &aad)?;
+
+    let decrypt_response = DecryptResponse {
+        plaintext: encode(&plaintext, Encoding::Base64)?,
+    };
+    serde_json::to_string(&decrypt_response).map_err(|_err| Error::InternalServerError)
+}
+
+pub fn decrypt(cipher: Cipher, key: &[u8], nonce: &[u8], ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>, Error> {
+    match cipher {
+        Cipher::Aes256Gcm => aead_decrypt::<Aes256Gcm>(key, nonce, ciphertext, aad),
+        Cipher::ChaCha20Poly1305 => aead_decrypt::<ChaCha20Poly1305>(key, nonce, ciphertext, aad),
+        Cipher::XChaCha20Poly1305 => aead_decrypt::<XChaCha20Poly1305>(key, nonce, ciphertext, aad),
+    }
+}
+
+// A wrong key, nonce, aad or a tampered ciphertext all fail the same tag check
+// and are indistinguishable by design.
+fn aead_decrypt<C: Aead + AeadCore + KeyInit>(key: &[u8], nonce: &[u8], ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>, Error> {
+    let cipher = C::new_from_slice(key).map_err(|_err| Error::InvalidKeyLength)?;
+    if nonce.len() != C::NonceSize::USIZE {
+        return Err(Error::InvalidKeyLength);
+    }
+
+    cipher
+        .decrypt(GenericArray::from_slice(nonce), Payload { msg: ciphertext, aad })
+        .map_err(|_err| Error::DecryptionFailed)
+}
+
+// Key rotation in one call, so the plaintext never leaves the Worker. The
+// response is that of `/encrypt`, with a fresh nonce.
+pub async fn reencrypt_handler(mut req: Request, config: &Config, cipher: Cipher) -> Result<String, Error> {
+    let reencrypt_req: ReencryptRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let ciphertext = decode(&reencrypt_req.ciphertext, Encoding::Base64)?;
+    let nonce = decode(&reencrypt_req.nonce, Encoding::Base64)?;
+    let old_key = decode(&reencrypt_req.old_key, Encoding::Base64)?;
+    let new_key = decode(&reencrypt_req.new_key, Encoding::Base64)?;
+    let aad = decode_aad(reencrypt_req.aad.as_deref())?;
+
+    let plaintext = decrypt(cipher, &old_key, &nonce, &ciphertext, &aad)?;
+    let (nonce, ciphertext) = encrypt(cipher, &new_key, &plaintext, &aad)?;
+
+    let encrypt_response = EncryptResponse {
+        ciphertext: encode(&ciphertext, Encoding::Base64)?,
+        nonce: encode(&nonce, Encoding::Base64)?,
+    };
+    serde_json::to_string(&encrypt_response).map_err(|_err| Error::InternalServerError)
+}
@@ -0,0 +1,347 @@
+//! Errors and their JSON responses.
+
+use worker::{Env, Response};
+
+use crate::random_handler::MAX_TOKEN_BYTES;
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct ErrorResponse {
+    pub error: ErrorBody,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct ErrorBody {
+    pub code: &'static str,
+    pub message: String,
+    /// Only with `VALIDATION_FAILED`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<FieldError>>,
+}
+
+// One of the problems `VALIDATION_FAILED` lists, named as within `options`.
+#[derive(serde::Serialize, Clone, schemars::JsonSchema)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+// RFC 7807 bodies, served as `application/problem+json` with `ERROR_FORMAT=problem`.
+#[derive(serde::Serialize)]
+pub struct ProblemResponse {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: &'static str,
+    pub status: u16,
+    pub detail: String,
+    pub instance: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<FieldError>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    Plain,
+    Problem,
+}
+
+// Memory pressure and busy hash slots in an isolate pass as soon as the
+// requests holding them finish.
+const RESOURCE_EXHAUSTED_RETRY_AFTER_SECONDS: u64 = 1;
+
+#[allow(clippy::enum_variant_names)]
+pub enum Error {
+    InvalidRoute,
+    MethodNotAllowed { allow: String },
+    BadRequest,
+    PayloadTooLarge,
+    InvalidBody(String),
+    UnsupportedMediaType,
+    InternalServerError,
+    InvalidHashOptions(String),
+    ConflictingOptions(String),
+    ValidationFailed(Vec<FieldError>),
+    HashFailed,
+    ResourceExhausted,
+    ServiceBusy,
+    RandomUnavailable,
+    InvalidPasswordHash,
+    VerifyFailed,
+    UnsupportedHashFormat,
+    AlgorithmMismatch,
+    EmptyPassword,
+    PasswordTooLong,
+    PasswordTooLongForBcrypt,
+    PasswordTooWeak,
+    PasswordBreached,
+    BatchTooLarge,
+    IdempotencyConflict,
+    InvalidEncoding,
+    InvalidRandomLength,
+    InvalidPasswordPolicy,
+    Unauthorized { realm: Option<String> },
+    RateLimited { retry_after: u64 },
+    UnsupportedAlgorithm,
+    InvalidKeyLength,
+    DecryptionFailed,
+    SigningKeyMissing,
+    InvalidConfiguration(String),
+    UpstreamUnavailable,
+}
+
+impl Error {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::InvalidRoute => "INVALID_ROUTE",
+            Error::MethodNotAllowed { .. } => "METHOD_NOT_ALLOWED",
+            Error::BadRequest => "BAD_REQUEST",
+            Error::PayloadTooLarge => "PAYLOAD_TOO_LARGE",
+            Error::InvalidBody(_) => "BAD_REQUEST",
+            Error::UnsupportedMediaType => "UNSUPPORTED_MEDIA_TYPE",
+            Error::InternalServerError => "INTERNAL_SERVER_ERROR",
+            Error::InvalidHashOptions(_) => "INVALID_HASH_OPTIONS",
+            Error::ConflictingOptions(_) => "CONFLICTING_OPTIONS",
+            Error::ValidationFailed(_) => "VALIDATION_FAILED",
+            Error::HashFailed => "HASH_FAILED",
+            Error::ResourceExhausted => "RESOURCE_EXHAUSTED",
+            Error::ServiceBusy => "SERVICE_BUSY",
+            Error::RandomUnavailable => "RANDOM_UNAVAILABLE",
+            Error::InvalidPasswordHash => "INVALID_PASSWORD_HASH",
+            Error::VerifyFailed => "VERIFY_FAILED",
+            Error::UnsupportedHashFormat => "UNSUPPORTED_HASH_FORMAT",
+            Error::AlgorithmMismatch => "ALGORITHM_MISMATCH",
+            Error::EmptyPassword => "EMPTY_PASSWORD",
+            Error::PasswordTooLong => "PASSWORD_TOO_LONG",
+            Error::PasswordTooLongForBcrypt => "PASSWORD_TOO_LONG_FOR_BCRYPT",
+            Error::PasswordTooWeak => "PASSWORD_TOO_WEAK",
+            Error::PasswordBreached => "PASSWORD_BREACHED",
+            Error::BatchTooLarge => "BATCH_TOO_LARGE",
+            Error::IdempotencyConflict => "IDEMPOTENCY_CONFLICT",
+            Error::InvalidEncoding => "INVALID_ENCODING",
+            Error::InvalidRandomLength => "INVALID_RANDOM_LENGTH",
+            Error::InvalidPasswordPolicy => "INVALID_PASSWORD_POLICY",
+            Error::Unauthorized { .. } => "UNAUTHORIZED",
+            Error::RateLimited { .. } => "RATE_LIMITED",
+            Error::UnsupportedAlgorithm => "UNSUPPORTED_ALGORITHM",
+            Error::InvalidKeyLength => "INVALID_KEY_LENGTH",
+            Error::DecryptionFailed => "DECRYPTION_FAILED",
+            Error::SigningKeyMissing => "SIGNING_KEY_MISSING",
+            Error::InvalidConfiguration(_) => "INVALID_CONFIGURATION",
+            Error::UpstreamUnavailable => "UPSTREAM_UNAVAILABLE",
+        }
+    }
+
+    // Stable per variant, unlike `message`, which may carry request details.
+    fn title(&self) -> &'static str {
+        match self {
+            Error::InvalidRoute => "Not found",
+            Error::MethodNotAllowed { .. } => "Method not allowed",
+            Error::BadRequest => "Bad request",
+            Error::PayloadTooLarge => "Payload too large",
+            Error::InvalidBody(_) => "Bad request",
+            Error::UnsupportedMediaType => "Unsupported media type",
+            Error::InternalServerError => "Internal server error",
+            Error::InvalidHashOptions(_) => "Invalid hash options",
+            Error::ConflictingOptions(_) => "Conflicting options",
+            Error::ValidationFailed(_) => "Validation failed",
+            Error::HashFailed => "Hash failed",
+            Error::ResourceExhausted => "Resource exhausted",
+            Error::ServiceBusy => "Service busy",
+            Error::RandomUnavailable => "Random number generator unavailable",
+            Error::InvalidPasswordHash => "Invalid password hash",
+            Error::VerifyFailed => "Verification failed",
+            Error::UnsupportedHashFormat => "Unsupported hash format",
+            Error::AlgorithmMismatch => "Algorithm mismatch",
+            Error::EmptyPassword => "Empty password",
+            Error::PasswordTooLong => "Password too long",
+            Error::PasswordTooLongForBcrypt => "Password too long for bcrypt",
+            Error::PasswordTooWeak => "Password too weak",
+            Error::PasswordBreached => "Password breached",
+            Error::BatchTooLarge => "Batch too large",
+            Error::IdempotencyConflict => "Idempotency conflict",
+            Error::InvalidEncoding => "Invalid encoding",
+            Error::InvalidRandomLength => "Invalid random length",
+            Error::InvalidPasswordPolicy => "Invalid password policy",
+            Error::Unauthorized { .. } => "Unauthorized",
+            Error::RateLimited { .. } => "Too many requests",
+            Error::UnsupportedAlgorithm => "Unsupported algorithm",
+            Error::InvalidKeyLength => "Invalid key length",
+            Error::DecryptionFailed => "Decryption failed",
+            Error::SigningKeyMissing => "Signing key missing",
+            Error::InvalidConfiguration(_) => "Invalid configuration",
+            Error::UpstreamUnavailable => "Upstream unavailable",
+        }
+    }
+
+    fn fields(&self) -> Option<Vec<FieldError>> {
+        match self {
+            Error::ValidationFailed(fields) => Some(fields.clone()),
+            _ => None,
+        }
+    }
+
+    // Derived from `code`, so variants sharing a code share a type.
+    fn problem_type(&self) -> String {
+        format!("urn:cryptoflare:error:{}", self.code().to_ascii_lowercase().replace('_', "-"))
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Error::InvalidRoute => "Not found.".into(),
+            Error::MethodNotAllowed { .. } => "Method not allowed.".into(),
+            Error::BadRequest => "Bad request.".into(),
+            Error::PayloadTooLarge => "Payload too large.".into(),
+            Error::InvalidBody(detail) => format!("Bad request: {detail}"),
+            Error::UnsupportedMediaType => "Unsupported media type.".into(),
+            Error::InternalServerError => "Internal server error.".into(),
+            Error::InvalidHashOptions(detail) => format!("Invalid option: {detail}"),
+            Error::ConflictingOptions(detail) => format!("Conflicting options: {detail}"),
+            Error::ValidationFailed(fields) => format!(
+                "Invalid options: {}",
+                fields.iter().map(|field| field.message.as_str()).collect::<Vec<_>>().join("; ")
+            ),
+            Error::HashFailed => "Hash failed.".into(),
+            Error::ResourceExhausted => "Not enough memory to hash right now, retry later.".into(),
+            Error::ServiceBusy => "Too many hashes in progress, retry later.".into(),
+            Error::RandomUnavailable => "Random number generator is unavailable.".into(),
+            Error::InvalidPasswordHash => "Invalid hash".into(),
+            Error::VerifyFailed => "Verification failed.".into(),
+            Error::UnsupportedHashFormat => "Unsupported hash format.".into(),
+            Error::AlgorithmMismatch => "Hash was made with a different algorithm than this endpoint verifies.".into(),
+            Error::EmptyPassword => "Password is empty.".into(),
+            Error::PasswordTooLong => "Password too long.".into(),
+            Error::PasswordTooLongForBcrypt => "Password exceeds bcrypt's 72 byte limit.".into(),
+            Error::PasswordTooWeak => "Password is too weak.".into(),
+            Error::PasswordBreached => "Password appears in a known data breach.".into(),
+            Error::BatchTooLarge => "Batch too large.".into(),
+            Error::IdempotencyConflict => "Idempotency-Key was already used for a different request.".into(),
+            Error::InvalidEncoding => "Invalid encoding.".into(),
+            Error::InvalidRandomLength => format!("Random length must be between 1 and {MAX_TOKEN_BYTES} bytes."),
+            Error::InvalidPasswordPolicy => "Password policy cannot be satisfied.".into(),
+            Error::Unauthorized { .. } => "Unauthorized.".into(),
+            Error::RateLimited { .. } => "Too many requests.".into(),
+            Error::UnsupportedAlgorithm => "Unsupported algorithm.".into(),
+            Error::InvalidKeyLength => "Invalid key length.".into(),
+            Error::DecryptionFailed => "Decryption failed.".into(),
+            Error::SigningKeyMissing => "Signing key is not configured.".into(),
+            Error::InvalidConfiguration(detail) => format!("Invalid configuration: {detail}"),
+            Error::UpstreamUnavailable => "Upstream service is unavailable.".into(),
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        match self {
+            Error::InvalidRoute => 404,
+            Error::MethodNotAllowed { .. } => 405,
+            Error::BadRequest => 400,
+            Error::PayloadTooLarge => 413,
+            Error::InvalidBody(_) => 400,
+            Error::UnsupportedMediaType => 415,
+            Error::InternalServerError => 500,
+            Error::InvalidHashOptions(_) => 400,
+            Error::ConflictingOptions(_) => 400,
+            Error::ValidationFailed(_) => 422,
+            Error::HashFailed => 500,
+            Error::ResourceExhausted => 503,
+            Error::ServiceBusy => 503,
+            Error::RandomUnavailable => 503,
+            Error::InvalidPasswordHash => 400,
+            Error::VerifyFailed => 500,
+            Error::UnsupportedHashFormat => 400,
+            Error::AlgorithmMismatch => 400,
+            Error::EmptyPassword => 422,
+            Error::PasswordTooLong => 413,
+            Error::PasswordTooLongForBcrypt => 400,
+            Error::PasswordTooWeak => 422,
+            Error::PasswordBreached => 422,
+            Error::BatchTooLarge => 413,
+            Error::IdempotencyConflict => 409,
+            Error::InvalidEncoding => 400,
+            Error::InvalidRandomLength => 400,
+            Error::InvalidPasswordPolicy => 400,
+            Error::Unauthorized { .. } => 401,
+            Error::RateLimited { .. } => 429,
+            Error::UnsupportedAlgorithm => 400,
+            Error::InvalidKeyLength => 400,
+            Error::DecryptionFailed => 400,
+            Error::SigningKeyMissing => 500,
+            Error::InvalidConfiguration(_) => 500,
+            Error::UpstreamUnavailable => 502,
+        }
+    }
+
+    pub fn to_response(&self, format: ErrorFormat, request_id: &str) -> worker::Result<Response> {
+        let mut response = match format {
+            ErrorFormat::Plain => {
+                let error_response = ErrorResponse {
+                    error: ErrorBody {
+                        code: self.code(),
+                        message: self.message(),
+                        fields: self.fields(),
+                    },
+                };
+                Response::from_json(&error_response)?
+            }
+            ErrorFormat::Problem => {
+                let problem_response = ProblemResponse {
+                    problem_type: self.problem_type(),
+                    title: self.title(),
+                    status: self.status(),
+                    detail: self.message(),
+                    instance: request_id.to_string(),
+                    fields: self.fields(),
+                };
+                let mut response = Response::from_json(&problem_response)?;
+                response.headers_mut().set("Content-Type", "application/problem+json")?;
+                response
+            }
+        }
+        .with_status(self.status());
+        response.headers_mut().set("Cache-Control", "no-store")?;
+
+        if let Error::RateLimited { retry_after } = self {
+            response.headers_mut().set("Retry-After", &retry_after.to_string())?;
+        }
+
+        if let Error::ResourceExhausted | Error::ServiceBusy = self {
+            response
+                .headers_mut()
+                .set("Retry-After", &RESOURCE_EXHAUSTED_RETRY_AFTER_SECONDS.to_string())?;
+        }
+
+        if let Error::Unauthorized { realm } = self {
+            let challenge = match realm {
+                Some(realm) => format!("Bearer realm=\"{}\"", realm.replace('\\', "\\\\").replace('"', "\\\"")),
+                None => "Bearer".to_string(),
+            };
+            response.headers_mut().set("WWW-Authenticate", &challenge)?;
+        }
+
+        if let Error::MethodNotAllowed { allow } = self {
+            response.headers_mut().set("Allow", allow)?;
+        }
+
+        Ok(response)
+    }
+}
+
+impl ErrorFormat {
+    // Read apart from `Config`, so configuration errors are shaped the same way.
+    pub fn from_env(env: &Env) -> Self {
+        match env.var("ERROR_FORMAT") {
+            Ok(var) if var.to_string() == "problem" => ErrorFormat::Problem,
+            _ => ErrorFormat::Plain,
+        }
+    }
+}
+
+// For the `field_errors` of the options types, which reuse the checks that
+// otherwise fail with `INVALID_HASH_OPTIONS`.
+pub fn push_field_error<T>(errors: &mut Vec<FieldError>, field: &'static str, result: Result<T, Error>) {
+    if let Err(err) = result {
+        let message = match err {
+            Error::InvalidHashOptions(detail) => detail,
+            err => err.message(),
+        };
+        errors.push(FieldError { field, message });
+    }
+}
@@ -0,0 +1,198 @@
+//! Hashing with the configured default algorithm or one chosen per request, and
+//! with argon2 and bcrypt at once.
+
+use serde_json::{json, Value};
+use worker::Request;
+
+use crate::argon2_handler::{argon2_hash, Argon2HashOptions};
+use crate::bcrypt_handler::{
+    bcrypt_hash, bcrypt_name, bcrypt_pre_hash, BcryptHashOptions, BCRYPT_MAX_PASSWORD_BYTES,
+};
+use crate::body::parse_body;
+use crate::config::Config;
+use crate::encoding::Encoding;
+use crate::error::Error;
+use crate::metrics;
+use crate::password::{
+    check_password_breached, check_password_control_chars, check_password_length,
+    check_password_present, check_password_strength, check_pre_hashed, decode_password, with_context,
+};
+use crate::scrypt_handler::{scrypt_hash, ScryptHashOptions};
+use crate::server_timing::ServerTiming;
+use crate::types::HashResponse;
+
+// ### Types
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GenericHashRequest {
+    pub password: String,
+    #[serde(default)]
+    pub password_encoding: Encoding,
+    /// `argon2id`, `bcrypt` or `scrypt`; defaults to `DEFAULT_HASH_ALGORITHM`.
+    pub algorithm: Option<String>,
+    /// The `options` of the chosen algorithm's own hash endpoint.
+    pub options: Option<Value>,
+    /// Hashed as `context || 0x00 || password`; must be the same on verify.
+    pub context: Option<String>,
+    /// `password` is its SHA-256 digest, see `password::decode_password`.
+    #[serde(default)]
+    pub pre_hashed: bool,
+    #[serde(default)]
+    pub include_timing: bool,
+    pub min_score: Option<u8>,
+    #[serde(default)]
+    pub reject_breached: bool,
+    #[serde(default)]
+    pub allow_empty: bool,
+    #[serde(default)]
+    pub reject_control_chars: bool,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DualHashRequest {
+    pub password: String,
+    #[serde(default)]
+    pub password_encoding: Encoding,
+    /// The `options` of `/argon2/hash`.
+    pub argon2_options: Option<Argon2HashOptions>,
+    /// The `options` of `/bcrypt/hash`.
+    pub bcrypt_options: Option<BcryptHashOptions>,
+    pub context: Option<String>,
+    /// `password` is its SHA-256 digest, see `password::decode_password`.
+    #[serde(default)]
+    pub pre_hashed: bool,
+    pub min_score: Option<u8>,
+    #[serde(default)]
+    pub reject_breached: bool,
+    #[serde(default)]
+    pub allow_empty: bool,
+    #[serde(default)]
+    pub reject_control_chars: bool,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct DualHashResponse {
+    pub argon2: String,
+    pub bcrypt: String,
+}
+
+// `options` only has a type once the algorithm is known, so they are
+// deserialized together after the default has been filled in.
+#[derive(serde::Deserialize)]
+#[serde(tag = "algorithm", content = "options", rename_all = "lowercase")]
+enum HashOptions {
+    Argon2id(Option<Argon2HashOptions>),
+    Bcrypt(Option<BcryptHashOptions>),
+    Scrypt(Option<ScryptHashOptions>),
+}
+
+pub const HASH_ALGORITHMS: [&str; 3] = ["argon2id", "bcrypt", "scrypt"];
+
+// ### Functions
+pub async fn hash_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let hash_req: GenericHashRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let algorithm = hash_req
+        .algorithm
+        .as_deref()
+        .unwrap_or(&config.default_hash_algorithm);
+    // `argon2id` is served by the `argon2` routes.
+    let route_algorithm = if algorithm == "argon2id" { "argon2" } else { algorithm };
+    if !HASH_ALGORITHMS.contains(&algorithm) || !config.algorithm_enabled(route_algorithm) {
+        return Err(Error::UnsupportedAlgorithm);
+    }
+    let options: HashOptions = serde_json::from_value(json!({
+        "algorithm": algorithm,
+        "options": hash_req.options,
+    }))
+    .map_err(|err| Error::InvalidBody(err.to_string()))?;
+
+    check_pre_hashed(hash_req.pre_hashed, hash_req.min_score, hash_req.reject_breached)?;
+    let password = decode_password(&hash_req.password, hash_req.password_encoding, hash_req.pre_hashed)?;
+    check_password_present(&password, hash_req.allow_empty)?;
+    check_password_control_chars(&password, hash_req.reject_control_chars)?;
+    check_password_length(&password, config.max_password_bytes)?;
+    check_password_strength(&password, hash_req.min_score)?;
+    if hash_req.reject_breached {
+        check_password_breached(&password).await?;
+    }
+    let password = with_context(&password, hash_req.context.as_deref());
+
+    let password_hash = hash_with(options, &password, config, timing).await?;
+    let elapsed_ms = hash_req
+        .include_timing
+        .then(|| timing.elapsed_ms());
+
+    let hash_response = HashResponse {
+        hash: password_hash,
+        elapsed_ms,
+    };
+    hash_response.to_json(config)
+}
+
+// For a dual-algorithm rollout: one request, one set of password checks, and
+// both hashes from the same bytes. Either failing fails the request.
+pub async fn dual_hash_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let dual_req: DualHashRequest = parse_body(&mut req, config.max_body_bytes).await?;
+    if !config.algorithm_enabled("argon2") || !config.algorithm_enabled("bcrypt") {
+        return Err(Error::UnsupportedAlgorithm);
+    }
+
+    check_pre_hashed(dual_req.pre_hashed, dual_req.min_score, dual_req.reject_breached)?;
+    let password = decode_password(&dual_req.password, dual_req.password_encoding, dual_req.pre_hashed)?;
+    check_password_present(&password, dual_req.allow_empty)?;
+    check_password_control_chars(&password, dual_req.reject_control_chars)?;
+    check_password_length(&password, config.max_password_bytes)?;
+    check_password_strength(&password, dual_req.min_score)?;
+    if dual_req.reject_breached {
+        check_password_breached(&password).await?;
+    }
+    let password = with_context(&password, dual_req.context.as_deref());
+
+    let dual_response = DualHashResponse {
+        argon2: hash_with(HashOptions::Argon2id(dual_req.argon2_options), &password, config, timing).await?,
+        bcrypt: hash_with(HashOptions::Bcrypt(dual_req.bcrypt_options), &password, config, timing).await?,
+    };
+    serde_json::to_string(&dual_response).map_err(|_err| Error::InternalServerError)
+}
+
+// Peppers, hashes and records the hash; the result carries the pepper version
+// if there is one.
+async fn hash_with(options: HashOptions, password: &[u8], config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let pepper = config.peppers.current()?;
+    let peppered = pepper.apply(password);
+    let password_hash = match options {
+        HashOptions::Argon2id(options) => {
+            let password_hash = timing.measure(|| {
+                argon2_hash(
+                    &peppered,
+                    options,
+                    &config.defaults,
+                    &config.argon2_limits,
+                    config.argon2_secret(),
+                )
+            })
+            .await?;
+            metrics::record_hash("argon2");
+            password_hash
+        }
+        HashOptions::Bcrypt(options) => {
+            let pre_hash_sha256 = options.as_ref().is_some_and(|opts| opts.pre_hash_sha256);
+            if !pre_hash_sha256 && password.len() > BCRYPT_MAX_PASSWORD_BYTES {
+                return Err(Error::PasswordTooLongForBcrypt);
+            }
+            let peppered = if pre_hash_sha256 { bcrypt_pre_hash(&peppered) } else { peppered };
+            let password_hash = timing.measure(|| bcrypt_hash(&peppered, options, &config.defaults, config.bcrypt_max_work_factor)).await?;
+            metrics::record_hash(bcrypt_name(pre_hash_sha256));
+            password_hash
+        }
+        HashOptions::Scrypt(options) => {
+            let password_hash = timing.measure(|| scrypt_hash(&peppered, options, &config.scrypt_limits)).await?;
+            metrics::record_hash("scrypt");
+            password_hash
+        }
+    };
+
+    Ok(pepper.tag(password_hash))
+}
@@ -0,0 +1,29 @@
+//! Health check listing the served algorithms.
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::routing::{route_enabled, ROUTES};
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct HealthResponse {
+    pub status: &'static str,
+    pub algorithms: Vec<&'static str>,
+    pub version: &'static str,
+}
+
+pub fn health_handler(config: &Config) -> Result<String, Error> {
+    let mut algorithms: Vec<&'static str> = Vec::new();
+    let enabled = ROUTES.iter().filter(|entry| route_enabled(entry, config));
+    for algorithm in enabled.filter_map(|entry| entry.algorithm) {
+        if !algorithms.contains(&algorithm) {
+            algorithms.push(algorithm);
+        }
+    }
+
+    let health_response = HealthResponse {
+        status: "ok",
+        algorithms,
+        version: env!("CARGO_PKG_VERSION"),
+    };
+    serde_json::to_string(&health_response).map_err(|_err| Error::InternalServerError)
+}
@@ -0,0 +1,70 @@
+//! `Idempotency-Key` support, caching responses in the `IDEMPOTENCY` KV
+//! namespace. Without the binding every request is treated as fresh.
+
+use sha2::{Digest, Sha256};
+use worker::{Env, kv::KvStore};
+
+use crate::error::Error;
+use crate::password::apply_pepper;
+
+// ### Types
+#[derive(serde::Serialize, serde::Deserialize)]
+struct IdempotencyRecord {
+    fingerprint: String,
+    response: String,
+}
+
+pub struct Idempotency {
+    kv: KvStore,
+    key: String,
+    fingerprint: String,
+}
+
+const IDEMPOTENCY_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+// ### Functions
+impl Idempotency {
+    // The fingerprint tells a retry apart from a different request reusing the
+    // key. It is keyed with the pepper when one is set, so KV never holds an
+    // offline-guessable digest of the passwords in `body`.
+    pub fn new(env: &Env, key: String, body: &[u8], pepper: Option<&str>) -> Option<Self> {
+        let kv = env.kv("IDEMPOTENCY").ok()?;
+        let fingerprint = hex::encode(Sha256::digest(apply_pepper(body, pepper)));
+
+        Some(Idempotency {
+            kv,
+            key: format!("idempotency:{key}"),
+            fingerprint,
+        })
+    }
+
+    pub async fn cached_response(&self) -> Result<Option<String>, Error> {
+        let record: Option<IdempotencyRecord> = self
+            .kv
+            .get(&self.key)
+            .json()
+            .await
+            .map_err(|_err| Error::InternalServerError)?;
+
+        match record {
+            Some(record) if record.fingerprint == self.fingerprint => Ok(Some(record.response)),
+            Some(_) => Err(Error::IdempotencyConflict),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn store_response(&self, response: &str) -> Result<(), Error> {
+        let record = IdempotencyRecord {
+            fingerprint: self.fingerprint.clone(),
+            response: response.to_string(),
+        };
+
+        self.kv
+            .put(&self.key, record)
+            .map_err(|_err| Error::InternalServerError)?
+            .expiration_ttl(IDEMPOTENCY_TTL_SECONDS)
+            .execute()
+            .await
+            .map_err(|_err| Error::InternalServerError)
+    }
+}
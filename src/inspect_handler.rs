@@ -0,0 +1,108 @@
+//! Introspection of stored hashes without verifying them.
+
+use argon2::password_hash::PasswordHash;
+use serde_json::{Map, Value};
+use worker::Request;
+
+use crate::body::parse_body;
+use crate::config::Config;
+use crate::error::Error;
+use crate::password::split_pepper_version;
+
+// ### Types
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct InspectRequest {
+    pub hash: String,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct InspectResponse {
+    pub algorithm: String,
+    pub version: Option<u32>,
+    pub params: Map<String, Value>,
+    pub salt_present: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pepper_version: Option<u32>,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct ValidateHashResponse {
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<String>,
+}
+
+// ### Functions
+// Whether the hash parses is the answer here, so a malformed one is
+// `valid: false` rather than an error. Nothing is hashed.
+pub async fn validate_hash_handler(mut req: Request, config: &Config) -> Result<String, Error> {
+    let validate_req: InspectRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let algorithm = inspect(&validate_req.hash)
+        .ok()
+        .map(|inspect_response| inspect_response.algorithm);
+    let validate_response = ValidateHashResponse {
+        valid: algorithm.is_some(),
+        algorithm,
+    };
+    serde_json::to_string(&validate_response).map_err(|_err| Error::InternalServerError)
+}
+
+pub async fn inspect_handler(mut req: Request, config: &Config) -> Result<String, Error> {
+    let inspect_req: InspectRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let inspect_response = inspect(&inspect_req.hash)?;
+    serde_json::to_string(&inspect_response).map_err(|_err| Error::InternalServerError)
+}
+
+pub fn inspect(hash: &str) -> Result<InspectResponse, Error> {
+    let (pepper_version, hash) = split_pepper_version(hash)?;
+
+    // bcrypt's `$2b$12$...` is not a PHC string, so it's parsed on its own
+    if ["$2a$", "$2b$", "$2y$"].iter().any(|prefix| hash.starts_with(prefix)) {
+        return inspect_bcrypt(hash, pepper_version);
+    }
+
+    let password_hash = PasswordHash::new(hash)
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+
+    // Numeric parameters are reported as numbers, anything else (argon2's
+    // `keyid` and `data`) verbatim.
+    let params = password_hash
+        .params
+        .iter()
+        .map(|(name, value)| {
+            let value = match value.decimal() {
+                Ok(decimal) => Value::from(decimal),
+                Err(_err) => Value::from(value.as_str()),
+            };
+            (name.to_string(), value)
+        })
+        .collect();
+
+    Ok(InspectResponse {
+        algorithm: password_hash.algorithm.to_string(),
+        version: password_hash.version,
+        params,
+        salt_present: password_hash.salt.is_some(),
+        pepper_version,
+    })
+}
+
+fn inspect_bcrypt(hash: &str, pepper_version: Option<u32>) -> Result<InspectResponse, Error> {
+    let hash_parts: bcrypt::HashParts = hash
+        .parse()
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+
+    let mut params = Map::new();
+    params.insert("work_factor".into(), Value::from(hash_parts.get_cost()));
+
+    Ok(InspectResponse {
+        algorithm: "bcrypt".into(),
+        version: None,
+        params,
+        salt_present: true,
+        pepper_version,
+    })
+}
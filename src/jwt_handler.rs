@@ -0,0 +1,140 @@
+//! HS256 JWT signing and verification.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL, Engine};
+use worker::{Date, Request};
+
+use crate::body::parse_body;
+use crate::config::Config;
+use crate::error::Error;
+use crate::hmac_handler::{HmacAlgorithm, hmac_compute, hmac_verify};
+
+// ### Types
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct JwtSignRequest {
+    pub claims: serde_json::Map<String, serde_json::Value>,
+    pub ttl_seconds: u64,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct JwtSignResponse {
+    pub token: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct JwtVerifyRequest {
+    pub token: String,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct JwtVerifyResponse {
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claims: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct JwtHeader {
+    pub alg: String,
+    pub typ: Option<String>,
+}
+
+// ### Functions
+pub async fn jwt_sign_handler(mut req: Request, config: &Config) -> Result<String, Error> {
+    let sign_req: JwtSignRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let secret = config.jwt_secret.as_deref().ok_or(Error::SigningKeyMissing)?;
+
+    let token = jwt_sign(sign_req.claims, sign_req.ttl_seconds, secret)?;
+
+    let sign_response = JwtSignResponse { token };
+    serde_json::to_string(&sign_response).map_err(|_err| Error::InternalServerError)
+}
+
+// Signs with HS256, overwriting any `iat` and `exp` in `claims`.
+fn jwt_sign(
+    mut claims: serde_json::Map<String, serde_json::Value>,
+    ttl_seconds: u64,
+    secret: &str,
+) -> Result<String, Error> {
+    let now = Date::now().as_millis() / 1000;
+    let exp = now.checked_add(ttl_seconds).ok_or(Error::BadRequest)?;
+    claims.insert("iat".into(), now.into());
+    claims.insert("exp".into(), exp.into());
+
+    let header = JwtHeader {
+        alg: "HS256".into(),
+        typ: Some("JWT".into()),
+    };
+    let header = serde_json::to_vec(&header).map_err(|_err| Error::InternalServerError)?;
+    let claims = serde_json::to_vec(&claims).map_err(|_err| Error::InternalServerError)?;
+
+    let signing_input = format!("{}.{}", BASE64URL.encode(header), BASE64URL.encode(claims));
+    let signature = hmac_compute(HmacAlgorithm::Sha256, secret.as_bytes(), signing_input.as_bytes());
+
+    Ok(format!("{signing_input}.{}", BASE64URL.encode(signature)))
+}
+
+pub async fn jwt_verify_handler(mut req: Request, config: &Config) -> Result<String, Error> {
+    let verify_req: JwtVerifyRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let secret = config.jwt_secret.as_deref().ok_or(Error::SigningKeyMissing)?;
+
+    let claims = jwt_verify(&verify_req.token, secret, config.jwt_leeway_seconds)?;
+
+    let verify_response = JwtVerifyResponse {
+        valid: claims.is_some(),
+        claims,
+    };
+    serde_json::to_string(&verify_response).map_err(|_err| Error::InternalServerError)
+}
+
+// Returns the claims of a valid token and `None` for a bad signature, a
+// non-HS256 `alg`, or an `exp`/`nbf` outside the leeway. Tokens that can't be
+// parsed at all are `Error::BadRequest`.
+fn jwt_verify(
+    token: &str,
+    secret: &str,
+    leeway_seconds: u64,
+) -> Result<Option<serde_json::Map<String, serde_json::Value>>, Error> {
+    let mut parts = token.split('.');
+    let (header, claims, signature) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(header), Some(claims), Some(signature), None) => (header, claims, signature),
+        _ => return Err(Error::BadRequest),
+    };
+
+    let header_bytes = BASE64URL.decode(header).map_err(|_err| Error::BadRequest)?;
+    let claims_bytes = BASE64URL.decode(claims).map_err(|_err| Error::BadRequest)?;
+    let signature = BASE64URL.decode(signature).map_err(|_err| Error::BadRequest)?;
+
+    let jwt_header: JwtHeader = serde_json::from_slice(&header_bytes).map_err(|_err| Error::BadRequest)?;
+    let claims_map: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_slice(&claims_bytes).map_err(|_err| Error::BadRequest)?;
+
+    if jwt_header.alg != "HS256" {
+        return Ok(None);
+    }
+
+    let signing_input = format!("{header}.{claims}");
+    if !hmac_verify(HmacAlgorithm::Sha256, secret.as_bytes(), signing_input.as_bytes(), &signature) {
+        return Ok(None);
+    }
+
+    let now = Date::now().as_millis() / 1000;
+    let timestamp = |name: &str| match claims_map.get(name) {
+        Some(value) => value.as_u64().map(Some).ok_or(Error::BadRequest),
+        None => Ok(None),
+    };
+
+    if let Some(exp) = timestamp("exp")? {
+        if now > exp.saturating_add(leeway_seconds) {
+            return Ok(None);
+        }
+    }
+    if let Some(nbf) = timestamp("nbf")? {
+        if now.saturating_add(leeway_seconds) < nbf {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(claims_map))
+}
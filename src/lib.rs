@@ -1,199 +1,104 @@
-use argon2::{
-    password_hash::{
-        rand_core::OsRng,
-        PasswordHash, PasswordHasher, PasswordVerifier, SaltString
-    },
-    Argon2, Params, Version,
-};
-use worker::{
-    Context, Env, Headers, Method, Request, Response,
-};
+mod argon2_handler;
+mod auth;
+mod balloon_handler;
+mod bcrypt_handler;
+mod body;
+mod concurrency;
+mod config;
+mod cors;
+mod digest_handler;
+mod ed25519_handler;
+mod encrypt_handler;
+mod encoding;
+mod error;
+mod hash_handler;
+mod health_handler;
+mod hmac_handler;
+mod idempotency;
+mod inspect_handler;
+mod jwt_handler;
+mod logging;
+mod metrics;
+mod nonce_handler;
+mod openapi_handler;
+mod password;
+mod pbkdf2_handler;
+mod random_handler;
+mod rate_limit;
+mod routing;
+mod scrypt_handler;
+mod server_timing;
+mod totp_handler;
+mod types;
+mod verify_handler;
+mod version_handler;
+
+use worker::{Context, Date, Env, Method, Request, Response};
+
+use auth::authenticate;
+use cors::{cors_preflight_response, set_cors_headers};
+use error::ErrorFormat;
+use logging::{generate_request_id, install_panic_hook, log_request, with_request_id};
+use rate_limit::check_rate_limit;
+use routing::dispatch;
+
+// The hash and verify functions don't touch the Worker runtime, so they can be
+// called (and tested) without a `Request`.
+pub use argon2_handler::{argon2_hash, argon2id_verify, Argon2HashOptions, Argon2Variant};
+pub use balloon_handler::{balloon_hash, balloon_verify, BalloonHashFunction, BalloonHashOptions};
+pub use bcrypt_handler::{bcrypt_hash, bcrypt_verify, BcryptHashOptions};
+pub use config::{Argon2Limits, Config, Defaults};
+pub use error::Error;
+pub use pbkdf2_handler::{Pbkdf2HashFunction, Pbkdf2HashOptions};
+pub use scrypt_handler::ScryptHashOptions;
+pub use types::*;
 
 #[worker::event(fetch)]
-async fn main(req: Request, _env: Env, _ctx: Context) -> worker::Result<Response> {
-    let result = match (req.method(), req.path().as_ref()) {
-        // argon2 defaults to argon2id
-        (Method::Post, "/argon2/hash") => argon2id_hash_handler(req).await,
-        (Method::Post, "/argon2/verify") => argon2id_verify_handler(req).await,
-        // TODO: maybe add the remaining argons
-        (Method::Post, "/bcrypt/hash") => bcrypt_hash_handler(req).await,
-        (Method::Post, "/bcrypt/verify") => bcrypt_verify_handler(req).await,
-        _ => Err(Error::InvalidRoute),
-    };
-
-    let mut res_headers = Headers::new();
-    res_headers.set("Content-Type", "application/json")?;
-
-    match result {
-        Ok(body) => Ok(Response::ok(body)?.with_headers(res_headers)),
-        Err(err) => err.to_response(),
-    }
-}
-
-// ## Hash
-// ### Types
-#[derive(serde::Deserialize)]
-pub struct HashRequest<T> {
-    pub password: String,
-    pub options: Option<T>,
-}
-
-#[derive(serde::Serialize)]
-pub struct HashResponse {
-    pub hash: String,
-}
-
-#[derive(serde::Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub struct Argon2HashOptions {
-    pub time_cost: u32,
-    pub memory_cost: u32,
-    pub parallelism: u32,
-}
+async fn main(req: Request, env: Env, _ctx: Context) -> worker::Result<Response> {
+    install_panic_hook();
+    let request_id = generate_request_id();
 
-#[derive(serde::Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub struct BcryptHashOptions {
-    pub work_factor: u32,
-}
-
-// ### Functions
-async fn argon2id_hash_handler(mut req: Request) -> Result<String, Error> {
-    let hash_req: HashRequest<Argon2HashOptions> = req
-        .json()
-        .await
-        .map_err(|_err| Error::BadRequest)?;
-
-    let password_hash = argon2id_hash(&hash_req.password, hash_req.options)?;
-
-    let hash_response = HashResponse {
-        hash: password_hash,
-    };
-    serde_json::to_string(&hash_response).map_err(|_err| Error::InternalServerError)
-}
-
-fn argon2id_hash(password: &str, options: Option<Argon2HashOptions>) -> Result<String, Error> {
-    let salt = SaltString::generate(&mut OsRng);
-
-    let argon2 = match options {
-        Some(opts) => {
-            let params = Params::new(
-                opts.memory_cost,
-                opts.time_cost,
-                opts.parallelism,
-                None,
-            ).map_err(|_err| Error::InvalidHashOptions)?;
-
-            Ok(Argon2::new(
-                argon2::Algorithm::Argon2id,
-                Version::default(),
-                params,
-            ))
-        }
-
-        None => Ok(Argon2::default()),
+    let mut response = match Config::from_env(&env) {
+        Ok(config) => serve(req, &env, &config, &request_id).await,
+        Err(err) => err.to_response(ErrorFormat::from_env(&env), &request_id),
     }?;
 
-    argon2
-        .hash_password(password.as_bytes(), &salt)
-        .map(|password_hash| password_hash.to_string())
-        .map_err(|_err| Error::HashFailed)
-}
+    response.headers_mut().set("X-Request-Id", &request_id)?;
 
-async fn bcrypt_hash_handler(mut req: Request) -> Result<String, Error> {
-    let hash_req: HashRequest<BcryptHashOptions> = req
-        .json()
-        .await
-        .map_err(|_err| Error::BadRequest)?;
-
-    let password_hash = match hash_req.options {
-        Some(opts) => Ok(bcrypt::hash(&hash_req.password, opts.work_factor)),
-        None => Ok(bcrypt::hash(&hash_req.password, bcrypt::DEFAULT_COST)),
-    }?;
-
-    let hash = password_hash
-        .map(|hash| hash.to_string())
-        .map_err(|_err| Error::HashFailed)?;
-
-    let hash_response = HashResponse {
-        hash: hash,
-    };
-    serde_json::to_string(&hash_response).map_err(|_err| Error::InternalServerError)
+    Ok(response)
 }
 
-// ## Verify
-// ### Types
-#[derive(serde::Deserialize)]
-pub struct VerifyRequest {
-    pub password: String,
-    pub hash: String,
-}
-
-#[derive(serde::Serialize)]
-pub struct VerifyResponse {
-    pub result: bool,
-}
+async fn serve(req: Request, env: &Env, config: &Config, request_id: &str) -> worker::Result<Response> {
+    let mut response = match &config.cors_allow_origin {
+        Some(_) if req.method() == Method::Options => cors_preflight_response(),
+        _ => match handle(req, env, config, request_id).await {
+            Ok(response) => Ok(response),
+            Err(err) => {
+                metrics::record_error(err.code());
+                err.to_response(config.error_format, request_id)
+            }
+        },
+    }?;
 
-// ### Functions
-async fn argon2id_verify_handler(mut req: Request) -> Result<String, Error> {
-    let options: VerifyRequest = req
-        .json()
-        .await
-        .map_err(|_err| Error::BadRequest)?;
+    if let Some(origin) = &config.cors_allow_origin {
+        set_cors_headers(response.headers_mut(), origin)?;
+    }
 
-    let result = argon2id_verify(&options)?;
-    let verify_response = VerifyResponse { result };
-    serde_json::to_string(&verify_response).map_err(|_err| Error::InternalServerError)
+    Ok(response)
 }
 
-fn argon2id_verify(options: &VerifyRequest) -> Result<bool, Error> {
-    let password_hash = PasswordHash::new(&options.hash)
-        .map_err(|_err| Error::InvalidPasswordHash)?;
+async fn handle(req: Request, env: &Env, config: &Config, request_id: &str) -> Result<Response, Error> {
+    let method = req.method();
+    let path = req.path();
+    let started = Date::now().as_millis();
 
-    let argon2 = Argon2::default();
-
-    match argon2.verify_password(options.password.as_bytes(), &password_hash) {
-        Ok(()) => Ok(true),
-
-        Err(err) => match err {
-            argon2::password_hash::Error::Password => Ok(false),
-            _ => Err(Error::VerifyFailed),
-        },
-    }
-}
-
-async fn bcrypt_verify_handler(mut req: Request) -> Result<String, Error> {
-    let options: VerifyRequest = req
-        .json()
-        .await
-        .map_err(|_err| Error::BadRequest)?;
+    let result = match check_rate_limit(&req, env, config).await.and_then(|()| authenticate(&req, env)) {
+        Ok(()) => with_request_id(request_id, dispatch(req, env, config)).await,
+        Err(err) => Err(err),
+    };
 
-    let result = bcrypt::verify(options.password, &options.hash).map_err(|_err| Error::VerifyFailed)?;
-    let verify_response = VerifyResponse { result };
-    serde_json::to_string(&verify_response).map_err(|_err| Error::InternalServerError)
-}
+    log_request(request_id, &method, &path, started, &result, config);
 
-// ## Error handling
-enum Error {
-    InvalidRoute,
-    BadRequest,
-    InternalServerError,
-    InvalidHashOptions,
-    HashFailed,
-    InvalidPasswordHash,
-    VerifyFailed,
+    result
 }
 
-impl Error {
-    fn to_response(&self) -> worker::Result<Response> {
-        match self {
-            Error::InvalidRoute => Response::error("Not found.", 404),
-            Error::BadRequest => Response::error("Bad request.", 400),
-            Error::InternalServerError => Response::error("Internal server error.", 500),
-            Error::InvalidHashOptions => Response::error("Invalid option for specified hash algorithm.", 400),
-            Error::HashFailed => Response::error("Hash failed.", 500),
-            Error::InvalidPasswordHash => Response::error("Invalid hash", 400),
-            Error::VerifyFailed => Response::error("Verification failed.", 500),
-        }
-    }
-}
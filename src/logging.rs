@@ -0,0 +1,112 @@
+//! One JSON line per request. Request bodies are never logged, so neither
+//! passwords nor hashes can end up in the logs.
+
+use std::cell::RefCell;
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Once;
+
+use worker::{Date, Method, Response};
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::random_handler::fill_random;
+use crate::routing::{request_route, routed_path};
+
+#[derive(serde::Serialize)]
+struct RequestLog<'a> {
+    request_id: &'a str,
+    method: &'a str,
+    path: &'a str,
+    algorithm: Option<&'static str>,
+    outcome: &'static str,
+    elapsed_ms: u64,
+}
+
+#[derive(serde::Serialize)]
+struct PanicLog<'a> {
+    request_id: &'a str,
+    location: Option<String>,
+}
+
+thread_local! {
+    // The request being polled, for the panic hook. An isolate interleaves
+    // requests, so this is set on every poll rather than once per request.
+    static CURRENT_REQUEST_ID: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+// Every response needs an id, even the one reporting `RANDOM_UNAVAILABLE`, so
+// without randomness ids are numbered per isolate instead. Those are only
+// unique within the isolate, which the prefix makes plain in the logs.
+pub fn generate_request_id() -> String {
+    static FALLBACK_IDS: AtomicU64 = AtomicU64::new(0);
+
+    let mut bytes = [0u8; 16];
+    match fill_random(&mut bytes) {
+        Ok(()) => uuid::Builder::from_random_bytes(bytes).into_uuid().to_string(),
+        Err(_err) => format!("local-{}", FALLBACK_IDS.fetch_add(1, Ordering::Relaxed)),
+    }
+}
+
+pub fn log_request(
+    request_id: &str,
+    method: &Method,
+    path: &str,
+    started: u64,
+    result: &Result<Response, Error>,
+    config: &Config,
+) {
+    // A path outside `ROUTE_PREFIX` matched nothing and is logged as sent.
+    let routed = routed_path(path, config).ok();
+    let log = RequestLog {
+        request_id,
+        method: method.as_ref(),
+        path: routed.as_deref().unwrap_or(path),
+        algorithm: routed
+            .as_deref()
+            .and_then(|routed| request_route(method, routed))
+            .and_then(|entry| entry.algorithm),
+        outcome: match result {
+            Ok(_) => "ok",
+            Err(err) => err.code(),
+        },
+        elapsed_ms: Date::now().as_millis() - started,
+    };
+
+    if let Ok(line) = serde_json::to_string(&log) {
+        worker::console_log!("{}", line);
+    }
+}
+
+// Only the panic's location is logged: its message could quote the data being
+// processed when it happened.
+pub fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        std::panic::set_hook(Box::new(|info| {
+            CURRENT_REQUEST_ID.with_borrow(|request_id| {
+                let log = PanicLog {
+                    request_id,
+                    location: info.location().map(ToString::to_string),
+                };
+                if let Ok(line) = serde_json::to_string(&log) {
+                    worker::console_error!("{}", line);
+                }
+            });
+        }));
+    });
+}
+
+// Makes `request_id` the one the panic hook logs while `future` is polled.
+pub async fn with_request_id<T>(request_id: &str, future: impl Future<Output = T>) -> T {
+    let mut future = pin!(future);
+    poll_fn(|cx| {
+        CURRENT_REQUEST_ID.with_borrow_mut(|current| {
+            current.clear();
+            current.push_str(request_id);
+        });
+        future.as_mut().poll(cx)
+    })
+    .await
+}
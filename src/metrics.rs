@@ -0,0 +1,125 @@
+//! Counters live in isolate memory: each isolate reports only what it served
+//! since it started, and counts reset whenever Cloudflare recycles it. Scrapes
+//! hit whichever isolate picks up the request, so treat the series as samples
+//! rather than totals.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::error::Error;
+
+struct Counters {
+    requests: BTreeMap<&'static str, u64>,
+    hashes: BTreeMap<&'static str, u64>,
+    verifications: BTreeMap<(&'static str, bool), u64>,
+    errors: BTreeMap<&'static str, u64>,
+}
+
+static COUNTERS: Mutex<Counters> = Mutex::new(Counters {
+    requests: BTreeMap::new(),
+    hashes: BTreeMap::new(),
+    verifications: BTreeMap::new(),
+    errors: BTreeMap::new(),
+});
+
+// Hash and verify requests currently being served by this isolate.
+static IN_FLIGHT: AtomicU64 = AtomicU64::new(0);
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct InFlightResponse {
+    pub count: u64,
+}
+
+// Decrements on drop, so a handler returning early or with an error is still
+// counted out.
+pub struct InFlightGuard;
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub fn track_in_flight() -> InFlightGuard {
+    IN_FLIGHT.fetch_add(1, Ordering::Relaxed);
+    InFlightGuard
+}
+
+fn with_counters(f: impl FnOnce(&mut Counters)) {
+    if let Ok(mut counters) = COUNTERS.lock() {
+        f(&mut counters);
+    }
+}
+
+pub fn record_request(route: &'static str) {
+    with_counters(|counters| *counters.requests.entry(route).or_default() += 1);
+}
+
+pub fn record_hash(algorithm: &'static str) {
+    with_counters(|counters| *counters.hashes.entry(algorithm).or_default() += 1);
+}
+
+pub fn record_verify(algorithm: &'static str, result: bool) {
+    with_counters(|counters| *counters.verifications.entry((algorithm, result)).or_default() += 1);
+}
+
+pub fn record_error(code: &'static str) {
+    with_counters(|counters| *counters.errors.entry(code).or_default() += 1);
+}
+
+// Prometheus text exposition format, version 0.0.4.
+pub fn render() -> String {
+    let mut out = String::new();
+
+    with_counters(|counters| {
+        let _ = writeln!(out, "# HELP cryptoflare_requests_total Requests served per route.");
+        let _ = writeln!(out, "# TYPE cryptoflare_requests_total counter");
+        for (route, count) in &counters.requests {
+            let _ = writeln!(out, "cryptoflare_requests_total{{route=\"{route}\"}} {count}");
+        }
+
+        let _ = writeln!(out, "# HELP cryptoflare_hashes_total Passwords hashed per algorithm.");
+        let _ = writeln!(out, "# TYPE cryptoflare_hashes_total counter");
+        for (algorithm, count) in &counters.hashes {
+            let _ = writeln!(out, "cryptoflare_hashes_total{{algorithm=\"{algorithm}\"}} {count}");
+        }
+
+        let _ = writeln!(out, "# HELP cryptoflare_verifications_total Password verifications per algorithm and result.");
+        let _ = writeln!(out, "# TYPE cryptoflare_verifications_total counter");
+        for ((algorithm, result), count) in &counters.verifications {
+            let result = if *result { "success" } else { "failure" };
+            let _ = writeln!(
+                out,
+                "cryptoflare_verifications_total{{algorithm=\"{algorithm}\",result=\"{result}\"}} {count}"
+            );
+        }
+
+        let _ = writeln!(out, "# HELP cryptoflare_errors_total Error responses per error code.");
+        let _ = writeln!(out, "# TYPE cryptoflare_errors_total counter");
+        for (code, count) in &counters.errors {
+            let _ = writeln!(out, "cryptoflare_errors_total{{code=\"{code}\"}} {count}");
+        }
+    });
+
+    let _ = writeln!(out, "# HELP cryptoflare_inflight_requests Hash and verify requests currently in flight.");
+    let _ = writeln!(out, "# TYPE cryptoflare_inflight_requests gauge");
+    let _ = writeln!(out, "cryptoflare_inflight_requests {}", IN_FLIGHT.load(Ordering::Relaxed));
+
+    out
+}
+
+pub fn inflight_handler() -> Result<String, Error> {
+    let inflight_response = InFlightResponse {
+        count: IN_FLIGHT.load(Ordering::Relaxed),
+    };
+    serde_json::to_string(&inflight_response).map_err(|_err| Error::InternalServerError)
+}
+
+// The Prometheus text exposition format.
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+pub fn metrics_handler() -> Result<String, Error> {
+    Ok(render())
+}
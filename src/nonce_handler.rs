@@ -0,0 +1,77 @@
+//! Stateless signed nonces for double-submit CSRF protection.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL, Engine};
+use worker::{Date, Request};
+
+use crate::body::parse_body;
+use crate::config::Config;
+use crate::error::Error;
+use crate::hmac_handler::{HmacAlgorithm, hmac_compute, hmac_verify};
+use crate::random_handler::fill_random;
+
+// ### Types
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct NonceResponse {
+    pub nonce: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct NonceVerifyRequest {
+    pub nonce: String,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct NonceVerifyResponse {
+    pub valid: bool,
+}
+
+const NONCE_RANDOM_BYTES: usize = 16;
+
+// ### Functions
+// A nonce is `<random>.<issued at>.<mac>`, the MAC covering the first two
+// parts, so nothing needs storing to check it later.
+pub fn nonce_handler(config: &Config) -> Result<String, Error> {
+    let secret = config.nonce_secret.as_deref().ok_or(Error::SigningKeyMissing)?;
+
+    let mut random = [0u8; NONCE_RANDOM_BYTES];
+    fill_random(&mut random)?;
+    let issued_at = Date::now().as_millis() / 1000;
+
+    let payload = format!("{}.{issued_at}", BASE64URL.encode(random));
+    let mac = hmac_compute(HmacAlgorithm::Sha256, secret.as_bytes(), payload.as_bytes());
+
+    let nonce_response = NonceResponse {
+        nonce: format!("{payload}.{}", BASE64URL.encode(mac)),
+    };
+    serde_json::to_string(&nonce_response).map_err(|_err| Error::InternalServerError)
+}
+
+pub async fn nonce_verify_handler(mut req: Request, config: &Config) -> Result<String, Error> {
+    let verify_req: NonceVerifyRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let secret = config.nonce_secret.as_deref().ok_or(Error::SigningKeyMissing)?;
+    let now = Date::now().as_millis() / 1000;
+
+    let valid = nonce_verify(&verify_req.nonce, secret, now, config.nonce_ttl_seconds);
+    let verify_response = NonceVerifyResponse { valid };
+    serde_json::to_string(&verify_response).map_err(|_err| Error::InternalServerError)
+}
+
+// Malformed, tampered and expired nonces are all just invalid. The MAC is
+// compared in constant time before the timestamp is trusted.
+fn nonce_verify(nonce: &str, secret: &str, now: u64, ttl_seconds: u64) -> bool {
+    let Some((payload, mac)) = nonce.rsplit_once('.') else {
+        return false;
+    };
+    let Ok(mac) = BASE64URL.decode(mac) else {
+        return false;
+    };
+    if !hmac_verify(HmacAlgorithm::Sha256, secret.as_bytes(), payload.as_bytes(), &mac) {
+        return false;
+    }
+
+    payload
+        .split_once('.')
+        .and_then(|(_random, issued_at)| issued_at.parse::<u64>().ok())
+        .is_some_and(|issued_at| issued_at <= now && now - issued_at <= ttl_seconds)
+}
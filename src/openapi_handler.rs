@@ -0,0 +1,294 @@
+//! OpenAPI 3.0 description of the routes, generated from their request and response types.
+
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::{Schema, SchemaObject, SubschemaValidation};
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+
+use crate::argon2_handler::{
+    Argon2BenchmarkRequest, Argon2BenchmarkResponse, Argon2ComponentsResponse, Argon2DeriveKeyRequest,
+    Argon2DeriveKeyResponse, Argon2DefaultParamsResponse, Argon2EstimateResponse, Argon2HashOptions,
+    Argon2VerifyAndAdviseRequest, Argon2VerifyAndAdviseResponse, Argon2VerifyRequest,
+    Argon2VerifySafeRequest, Argon2WarmupResponse,
+};
+use crate::balloon_handler::BalloonHashOptions;
+use crate::bcrypt_handler::{BcryptDefaultParamsResponse, BcryptHashOptions};
+use crate::digest_handler::{Blake3Request, DigestRequest, DigestResponse, DigestStreamQuery};
+use crate::ed25519_handler::{
+    Ed25519SignRequest, Ed25519SignResponse, Ed25519VerifyRequest, Ed25519VerifyResponse,
+};
+use crate::encoding::{ConvertRequest, ConvertResponse};
+use crate::encrypt_handler::{
+    DecryptRequest, DecryptResponse, EncryptRequest, EncryptResponse, ReencryptRequest,
+};
+use crate::error::{Error, ErrorResponse};
+use crate::hash_handler::{DualHashRequest, DualHashResponse, GenericHashRequest};
+use crate::health_handler::HealthResponse;
+use crate::hmac_handler::{HmacRequest, HmacResponse, HmacVerifyRequest};
+use crate::inspect_handler::{InspectRequest, InspectResponse, ValidateHashResponse};
+use crate::jwt_handler::{JwtSignRequest, JwtSignResponse, JwtVerifyRequest, JwtVerifyResponse};
+use crate::metrics::InFlightResponse;
+use crate::nonce_handler::{NonceResponse, NonceVerifyRequest, NonceVerifyResponse};
+use crate::password::{
+    PasswordBreachedRequest, PasswordBreachedResponse, PasswordStrengthRequest, PasswordStrengthResponse,
+};
+use crate::pbkdf2_handler::Pbkdf2HashOptions;
+use crate::random_handler::{
+    RandomPasswordRequest, RandomPasswordResponse, RandomSaltQuery, RandomSaltResponse, RandomTokenQuery,
+    RandomTokenResponse,
+};
+use crate::routing::{Route, ROUTES};
+use crate::scrypt_handler::ScryptHashOptions;
+use crate::totp_handler::{TotpGenerateRequest, TotpGenerateResponse, TotpVerifyRequest, TotpVerifyResponse};
+use crate::types::{
+    BatchHashRequest, BatchHashResponse, HashRequest, HashResponse, NeedsRehashRequest, NeedsRehashResponse,
+    VerifyRequest, VerifyResponse,
+};
+use crate::verify_handler::{BatchVerifyRequest, BatchVerifyResponse, MultiVerifyRequest, MultiVerifyResponse};
+use crate::version_handler::VersionResponse;
+
+// ### Types
+enum Body {
+    None,
+    Json(Schema),
+    Binary,
+    Text,
+}
+
+struct Operation {
+    parameters: Vec<Value>,
+    request: Body,
+    response: Body,
+}
+
+// ### Functions
+pub fn openapi_handler() -> Result<String, Error> {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let error = gen.subschema_for::<ErrorResponse>();
+
+    let mut paths = Map::new();
+    for entry in ROUTES {
+        let operation = operation(entry.route, &mut gen);
+        let item = paths
+            .entry(entry.path)
+            .or_insert_with(|| Value::Object(Map::new()));
+        item[entry.method.as_ref().to_ascii_lowercase()] = operation_object(operation, &error);
+    }
+
+    let document = json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": gen.take_definitions(),
+        },
+    });
+    serde_json::to_string(&document).map_err(|_err| Error::InternalServerError)
+}
+
+fn operation_object(operation: Operation, error: &Schema) -> Value {
+    let mut object = json!({
+        "responses": {
+            "200": response_object("Success", operation.response),
+            "default": response_object("Error", Body::Json(error.clone())),
+        },
+    });
+    if !operation.parameters.is_empty() {
+        object["parameters"] = Value::Array(operation.parameters);
+    }
+    // Every JSON body is also accepted form-encoded, see `body::parse_body_with_raw`.
+    let content = match operation.request {
+        Body::None => None,
+        Body::Json(schema) => Some(json!({
+            "application/json": { "schema": schema },
+            "application/x-www-form-urlencoded": { "schema": schema },
+        })),
+        Body::Binary => Some(json!({
+            "application/octet-stream": { "schema": { "type": "string", "format": "binary" } },
+        })),
+        Body::Text => Some(json!({
+            "text/plain": { "schema": { "type": "string" } },
+        })),
+    };
+    if let Some(content) = content {
+        object["requestBody"] = json!({ "required": true, "content": content });
+    }
+    object
+}
+
+fn response_object(description: &str, body: Body) -> Value {
+    let content = match body {
+        Body::None => return json!({ "description": description }),
+        Body::Json(schema) => json!({ "application/json": { "schema": schema } }),
+        Body::Binary => json!({ "application/octet-stream": { "schema": { "type": "string", "format": "binary" } } }),
+        Body::Text => json!({ "text/plain": { "schema": { "type": "string" } } }),
+    };
+    json!({ "description": description, "content": content })
+}
+
+fn json_body<T: JsonSchema>(gen: &mut SchemaGenerator) -> Body {
+    Body::Json(gen.subschema_for::<T>())
+}
+
+// For responses whose shape depends on the request, e.g. `output_format`.
+fn one_of(schemas: Vec<Schema>) -> Schema {
+    Schema::Object(SchemaObject {
+        subschemas: Some(Box::new(SubschemaValidation {
+            one_of: Some(schemas),
+            ..Default::default()
+        })),
+        ..Default::default()
+    })
+}
+
+fn op(request: Body, response: Body) -> Operation {
+    Operation {
+        parameters: Vec::new(),
+        request,
+        response,
+    }
+}
+
+// Query strings are described by the same structs the handlers deserialize
+// them into; each property becomes one parameter.
+fn query_parameters<T: JsonSchema>(gen: &mut SchemaGenerator) -> Vec<Value> {
+    let Schema::Object(schema) = T::json_schema(gen) else {
+        return Vec::new();
+    };
+    let Some(object) = schema.object else {
+        return Vec::new();
+    };
+
+    object
+        .properties
+        .into_iter()
+        .map(|(name, schema)| {
+            json!({
+                "name": name,
+                "in": "query",
+                "required": object.required.contains(&name),
+                "schema": schema,
+            })
+        })
+        .collect()
+}
+
+fn operation(route: Route, gen: &mut SchemaGenerator) -> Operation {
+    match route {
+        Route::Argon2Hash => op(
+            json_body::<HashRequest<Argon2HashOptions>>(gen),
+            Body::Json(one_of(vec![
+                gen.subschema_for::<HashResponse>(),
+                gen.subschema_for::<Argon2ComponentsResponse>(),
+            ])),
+        ),
+        Route::Argon2HashText => Operation {
+            parameters: query_parameters::<Argon2HashOptions>(gen),
+            request: Body::Text,
+            response: Body::Json(one_of(vec![
+                gen.subschema_for::<HashResponse>(),
+                gen.subschema_for::<Argon2ComponentsResponse>(),
+            ])),
+        },
+        Route::Argon2HashBatch => op(
+            json_body::<BatchHashRequest<Argon2HashOptions>>(gen),
+            json_body::<BatchHashResponse>(gen),
+        ),
+        Route::Argon2Verify => op(json_body::<Argon2VerifyRequest>(gen), json_body::<VerifyResponse>(gen)),
+        Route::BcryptVerify
+        | Route::BcryptSha256Verify
+        | Route::ScryptVerify
+        | Route::Pbkdf2Verify
+        | Route::BalloonVerify
+        | Route::Verify => op(json_body::<VerifyRequest>(gen), json_body::<VerifyResponse>(gen)),
+        Route::VerifyBatch => op(json_body::<BatchVerifyRequest>(gen), json_body::<BatchVerifyResponse>(gen)),
+        Route::VerifyMulti => op(json_body::<MultiVerifyRequest>(gen), json_body::<MultiVerifyResponse>(gen)),
+        Route::Argon2VerifySafe => op(json_body::<Argon2VerifySafeRequest>(gen), json_body::<VerifyResponse>(gen)),
+        Route::Argon2NeedsRehash => op(
+            json_body::<NeedsRehashRequest<Argon2HashOptions>>(gen),
+            json_body::<NeedsRehashResponse>(gen),
+        ),
+        Route::Argon2Estimate => op(json_body::<Argon2HashOptions>(gen), json_body::<Argon2EstimateResponse>(gen)),
+        Route::Argon2Benchmark => op(
+            json_body::<Argon2BenchmarkRequest>(gen),
+            json_body::<Argon2BenchmarkResponse>(gen),
+        ),
+        Route::Argon2DeriveKey => op(
+            json_body::<Argon2DeriveKeyRequest>(gen),
+            json_body::<Argon2DeriveKeyResponse>(gen),
+        ),
+        Route::Argon2VerifyAndAdvise => op(
+            json_body::<Argon2VerifyAndAdviseRequest>(gen),
+            json_body::<Argon2VerifyAndAdviseResponse>(gen),
+        ),
+        Route::Argon2DefaultParams => op(Body::None, json_body::<Argon2DefaultParamsResponse>(gen)),
+        Route::Argon2Warmup => op(Body::None, json_body::<Argon2WarmupResponse>(gen)),
+        Route::BcryptHash | Route::BcryptSha256Hash => {
+            op(json_body::<HashRequest<BcryptHashOptions>>(gen), json_body::<HashResponse>(gen))
+        }
+        Route::BcryptNeedsRehash => op(
+            json_body::<NeedsRehashRequest<BcryptHashOptions>>(gen),
+            json_body::<NeedsRehashResponse>(gen),
+        ),
+        Route::BcryptDefaultParams => op(Body::None, json_body::<BcryptDefaultParamsResponse>(gen)),
+        Route::ScryptHash => op(json_body::<HashRequest<ScryptHashOptions>>(gen), json_body::<HashResponse>(gen)),
+        Route::Pbkdf2Hash => op(json_body::<HashRequest<Pbkdf2HashOptions>>(gen), json_body::<HashResponse>(gen)),
+        Route::BalloonHash => op(json_body::<HashRequest<BalloonHashOptions>>(gen), json_body::<HashResponse>(gen)),
+        Route::Hash => op(json_body::<GenericHashRequest>(gen), json_body::<HashResponse>(gen)),
+        Route::HashDual => op(json_body::<DualHashRequest>(gen), json_body::<DualHashResponse>(gen)),
+        Route::Inspect => op(json_body::<InspectRequest>(gen), json_body::<InspectResponse>(gen)),
+        Route::ValidateHash => op(json_body::<InspectRequest>(gen), json_body::<ValidateHashResponse>(gen)),
+        Route::Hmac => op(json_body::<HmacRequest>(gen), json_body::<HmacResponse>(gen)),
+        Route::HmacVerify => op(json_body::<HmacVerifyRequest>(gen), json_body::<VerifyResponse>(gen)),
+        Route::Encrypt | Route::EncryptChaCha20Poly1305 | Route::EncryptXChaCha20Poly1305 => {
+            op(json_body::<EncryptRequest>(gen), json_body::<EncryptResponse>(gen))
+        }
+        Route::Decrypt | Route::DecryptChaCha20Poly1305 | Route::DecryptXChaCha20Poly1305 => {
+            op(json_body::<DecryptRequest>(gen), json_body::<DecryptResponse>(gen))
+        }
+        Route::Reencrypt => op(json_body::<ReencryptRequest>(gen), json_body::<EncryptResponse>(gen)),
+        Route::Digest => op(json_body::<DigestRequest>(gen), json_body::<DigestResponse>(gen)),
+        Route::DigestStream => Operation {
+            parameters: query_parameters::<DigestStreamQuery>(gen),
+            request: Body::Binary,
+            response: json_body::<DigestResponse>(gen),
+        },
+        Route::Blake3 => op(json_body::<Blake3Request>(gen), json_body::<DigestResponse>(gen)),
+        Route::Encode | Route::Decode => op(json_body::<ConvertRequest>(gen), json_body::<ConvertResponse>(gen)),
+        Route::JwtSign => op(json_body::<JwtSignRequest>(gen), json_body::<JwtSignResponse>(gen)),
+        Route::JwtVerify => op(json_body::<JwtVerifyRequest>(gen), json_body::<JwtVerifyResponse>(gen)),
+        Route::Ed25519Sign => op(json_body::<Ed25519SignRequest>(gen), json_body::<Ed25519SignResponse>(gen)),
+        Route::Ed25519Verify => op(json_body::<Ed25519VerifyRequest>(gen), json_body::<Ed25519VerifyResponse>(gen)),
+        Route::TotpGenerate => op(json_body::<TotpGenerateRequest>(gen), json_body::<TotpGenerateResponse>(gen)),
+        Route::TotpVerify => op(json_body::<TotpVerifyRequest>(gen), json_body::<TotpVerifyResponse>(gen)),
+        Route::RandomToken => Operation {
+            parameters: query_parameters::<RandomTokenQuery>(gen),
+            request: Body::None,
+            response: json_body::<RandomTokenResponse>(gen),
+        },
+        Route::RandomPassword => op(json_body::<RandomPasswordRequest>(gen), json_body::<RandomPasswordResponse>(gen)),
+        Route::RandomSalt => Operation {
+            parameters: query_parameters::<RandomSaltQuery>(gen),
+            request: Body::None,
+            response: json_body::<RandomSaltResponse>(gen),
+        },
+        Route::Nonce => op(Body::None, json_body::<NonceResponse>(gen)),
+        Route::NonceVerify => op(json_body::<NonceVerifyRequest>(gen), json_body::<NonceVerifyResponse>(gen)),
+        Route::PasswordStrength => op(
+            json_body::<PasswordStrengthRequest>(gen),
+            json_body::<PasswordStrengthResponse>(gen),
+        ),
+        Route::PasswordBreached => op(
+            json_body::<PasswordBreachedRequest>(gen),
+            json_body::<PasswordBreachedResponse>(gen),
+        ),
+        Route::Health => op(Body::None, json_body::<HealthResponse>(gen)),
+        Route::Version => op(Body::None, json_body::<VersionResponse>(gen)),
+        Route::Metrics => op(Body::None, Body::Text),
+        Route::InFlight => op(Body::None, json_body::<InFlightResponse>(gen)),
+        Route::OpenApi => op(Body::None, Body::Json(Schema::Bool(true))),
+    }
+}
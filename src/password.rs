@@ -0,0 +1,314 @@
+//! Password policy checks, the pepper and the `/password/*` endpoints.
+
+use sha1::Sha1;
+use sha2::Digest;
+use worker::{Fetch, Headers, Request, RequestInit};
+
+use crate::body::parse_body;
+use crate::config::{Config, Peppers};
+use crate::encoding::{decode, Encoding};
+use crate::error::Error;
+use crate::hmac_handler::{HmacAlgorithm, hmac_compute};
+
+// An empty password is almost always a client bug, so hashing one is opt-in.
+// Verify doesn't check: an empty password simply doesn't match.
+pub fn check_password_present(password: &[u8], allow_empty: bool) -> Result<(), Error> {
+    if password.is_empty() && !allow_empty {
+        return Err(Error::EmptyPassword);
+    }
+
+    Ok(())
+}
+
+// NUL and other C0 control characters hash fine but trip up systems the same
+// credentials are later submitted to; tab and newline are left alone.
+pub fn check_password_control_chars(password: &[u8], reject_control_chars: bool) -> Result<(), Error> {
+    let is_control = |byte: &u8| *byte < 0x20 && !matches!(byte, b'\t' | b'\n');
+    if reject_control_chars && password.iter().any(is_control) {
+        return Err(Error::InvalidBody(
+            "password contains control characters (0x00-0x1F other than tab and newline)".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn check_password_length(password: &[u8], max_password_bytes: usize) -> Result<(), Error> {
+    if password.len() > max_password_bytes {
+        return Err(Error::PasswordTooLong);
+    }
+
+    Ok(())
+}
+
+// ## Pepper
+// A pepper is an HMAC-SHA256 key applied to the password before hashing and
+// verifying. `HASH_PEPPER` is unversioned. Once `CURRENT_PEPPER_VERSION` is
+// set, new hashes use the `PEPPER_V<n>` secret and are stored as
+// `$pepper$v=<n>` followed by the hash itself, e.g.
+// `$pepper$v=2$argon2id$v=19$...`. Verify reads the version back to pick the
+// same secret, so peppers can be rotated while older hashes keep verifying;
+// hashes without the prefix are verified with `HASH_PEPPER`.
+// ### Types
+#[derive(Clone, Copy)]
+pub struct Pepper<'a> {
+    pub version: Option<u32>,
+    pub secret: Option<&'a str>,
+}
+
+const PEPPER_VERSION_PREFIX: &str = "$pepper$v=";
+
+// ### Functions
+impl Peppers {
+    // The pepper new hashes are made with.
+    pub fn current(&self) -> Result<Pepper<'_>, Error> {
+        self.get(self.current_version)
+    }
+
+    // The pepper `hash` was made with, and the hash without its version prefix.
+    pub fn for_hash<'hash>(&self, hash: &'hash str) -> Result<(Pepper<'_>, &'hash str), Error> {
+        let (version, hash) = split_pepper_version(hash)?;
+        Ok((self.get(version)?, hash))
+    }
+
+    fn get(&self, version: Option<u32>) -> Result<Pepper<'_>, Error> {
+        let secret = match version {
+            Some(version) => Some(
+                self.versioned
+                    .get(&version)
+                    .ok_or(Error::SigningKeyMissing)?
+                    .as_str(),
+            ),
+            None => self.unversioned.as_deref(),
+        };
+
+        Ok(Pepper { version, secret })
+    }
+}
+
+impl Pepper<'_> {
+    pub fn apply(&self, password: &[u8]) -> Vec<u8> {
+        apply_pepper(password, self.secret)
+    }
+
+    // Prefixes a hash made with this pepper with its version, if it has one.
+    pub fn tag(&self, hash: String) -> String {
+        tag_pepper_version(hash, self.version)
+    }
+}
+
+pub fn tag_pepper_version(hash: String, version: Option<u32>) -> String {
+    match version {
+        Some(version) => format!("{PEPPER_VERSION_PREFIX}{version}{hash}"),
+        None => hash,
+    }
+}
+
+// Reading the version needs no secret, so rehash checks and `/inspect` don't
+// depend on the pepper being configured.
+pub fn split_pepper_version(hash: &str) -> Result<(Option<u32>, &str), Error> {
+    let Some(tagged) = hash.strip_prefix(PEPPER_VERSION_PREFIX) else {
+        return Ok((None, hash));
+    };
+
+    let (version, hash) = tagged
+        .find('$')
+        .map(|end| tagged.split_at(end))
+        .ok_or(Error::InvalidPasswordHash)?;
+    let version = version
+        .parse()
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+
+    Ok((Some(version), hash))
+}
+
+pub fn apply_pepper(password: &[u8], pepper: Option<&str>) -> Vec<u8> {
+    match pepper {
+        Some(pepper) => hmac_compute(HmacAlgorithm::Sha256, pepper.as_bytes(), password),
+
+        None => password.to_vec(),
+    }
+}
+
+// ## Context
+// Domain separation between kinds of secret hashed by one deployment, with
+// the same pepper; unlike argon2's `data` it works for every algorithm. The
+// NUL keeps `("ab", "c")` and `("a", "bc")` apart.
+pub fn with_context(password: &[u8], context: Option<&str>) -> Vec<u8> {
+    match context {
+        Some(context) => [context.as_bytes(), &[0], password].concat(),
+        None => password.to_vec(),
+    }
+}
+
+// ## Pre-hashed passwords
+// Some clients may only send SHA-256(password). That digest is then the
+// password as far as hashing goes, so a hash made from one only verifies with
+// `pre_hashed` too. Unless `password_encoding` names another encoding it is hex
+// (64 characters) or standard base64.
+pub fn decode_password(password: &str, encoding: Encoding, pre_hashed: bool) -> Result<Vec<u8>, Error> {
+    if !pre_hashed {
+        return decode(password, encoding);
+    }
+
+    let encoding = match encoding {
+        Encoding::Utf8 if password.len() == 2 * PRE_HASH_LEN => Encoding::Hex,
+        Encoding::Utf8 => Encoding::Base64,
+        encoding => encoding,
+    };
+    let digest = decode(password, encoding)?;
+    if digest.len() != PRE_HASH_LEN {
+        return Err(Error::InvalidEncoding);
+    }
+
+    Ok(digest)
+}
+
+const PRE_HASH_LEN: usize = 32;
+
+// Strength and breach checks would only ever see the digest.
+pub fn check_pre_hashed(pre_hashed: bool, min_score: Option<u8>, reject_breached: bool) -> Result<(), Error> {
+    if pre_hashed && (min_score.is_some() || reject_breached) {
+        return Err(Error::ConflictingOptions(
+            "min_score and reject_breached can't check a pre_hashed password".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+// ## Password strength
+// Estimates come from zxcvbn. It is comparatively slow, so the hash handlers
+// only run it when the request sets `min_score`.
+// ### Types
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct PasswordStrengthRequest {
+    pub password: String,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct PasswordStrengthResponse {
+    pub score: u8,
+    pub guesses_log10: f64,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+const MAX_PASSWORD_SCORE: u8 = 4;
+
+// ### Functions
+pub async fn password_strength_handler(mut req: Request, config: &Config) -> Result<String, Error> {
+    let strength_req: PasswordStrengthRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    check_password_length(strength_req.password.as_bytes(), config.max_password_bytes)?;
+
+    let entropy = zxcvbn::zxcvbn(&strength_req.password, &[]);
+    let feedback = entropy.feedback();
+
+    let strength_response = PasswordStrengthResponse {
+        score: entropy.score().into(),
+        guesses_log10: entropy.guesses_log10(),
+        warning: feedback
+            .and_then(|feedback| feedback.warning())
+            .map(|warning| warning.to_string()),
+        suggestions: feedback
+            .map(|feedback| feedback.suggestions().iter().map(ToString::to_string).collect())
+            .unwrap_or_default(),
+    };
+    serde_json::to_string(&strength_response).map_err(|_err| Error::InternalServerError)
+}
+
+pub fn check_password_strength(password: &[u8], min_score: Option<u8>) -> Result<(), Error> {
+    let Some(min_score) = min_score else {
+        return Ok(());
+    };
+
+    if min_score > MAX_PASSWORD_SCORE {
+        return Err(Error::InvalidHashOptions(format!(
+            "min_score must be between 0 and {MAX_PASSWORD_SCORE}"
+        )));
+    }
+
+    if u8::from(zxcvbn::zxcvbn(&String::from_utf8_lossy(password), &[]).score()) < min_score {
+        return Err(Error::PasswordTooWeak);
+    }
+
+    Ok(())
+}
+
+// ## Breached passwords
+// Lookups use the Pwned Passwords range API: only the first 5 hex characters of
+// the SHA-1 digest leave the worker, and the suffix is matched locally.
+// ### Types
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct PasswordBreachedRequest {
+    pub password: String,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct PasswordBreachedResponse {
+    pub breached: bool,
+    pub count: u64,
+}
+
+const PWNED_PASSWORDS_RANGE_URL: &str = "https://api.pwnedpasswords.com/range/";
+
+// ### Functions
+pub async fn password_breached_handler(mut req: Request, config: &Config) -> Result<String, Error> {
+    let breached_req: PasswordBreachedRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    check_password_length(breached_req.password.as_bytes(), config.max_password_bytes)?;
+
+    let count = pwned_count(breached_req.password.as_bytes()).await?;
+
+    let breached_response = PasswordBreachedResponse {
+        breached: count > 0,
+        count,
+    };
+    serde_json::to_string(&breached_response).map_err(|_err| Error::InternalServerError)
+}
+
+pub async fn check_password_breached(password: &[u8]) -> Result<(), Error> {
+    if pwned_count(password).await? > 0 {
+        return Err(Error::PasswordBreached);
+    }
+
+    Ok(())
+}
+
+async fn pwned_count(password: &[u8]) -> Result<u64, Error> {
+    let digest = hex::encode_upper(Sha1::digest(password));
+    let (prefix, suffix) = digest.split_at(5);
+
+    // Padding hides how many suffixes the range really has; padded entries
+    // always have a count of 0.
+    let mut headers = Headers::new();
+    headers
+        .set("Add-Padding", "true")
+        .map_err(|_err| Error::InternalServerError)?;
+    let mut init = RequestInit::new();
+    init.with_headers(headers);
+
+    let upstream_req = Request::new_with_init(&format!("{PWNED_PASSWORDS_RANGE_URL}{prefix}"), &init)
+        .map_err(|_err| Error::InternalServerError)?;
+    let mut upstream_res = Fetch::Request(upstream_req)
+        .send()
+        .await
+        .map_err(|_err| Error::UpstreamUnavailable)?;
+    if upstream_res.status_code() != 200 {
+        return Err(Error::UpstreamUnavailable);
+    }
+    let body = upstream_res
+        .text()
+        .await
+        .map_err(|_err| Error::UpstreamUnavailable)?;
+
+    let count = body
+        .lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .find(|(candidate, _)| candidate.eq_ignore_ascii_case(suffix))
+        .map(|(_, count)| count.parse::<u64>().map_err(|_err| Error::UpstreamUnavailable))
+        .transpose()?;
+
+    Ok(count.unwrap_or(0))
+}
@@ -0,0 +1,141 @@
+//! PBKDF2 hashing and verification.
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier};
+use pbkdf2::Pbkdf2;
+use worker::Request;
+
+use crate::body::parse_body;
+use crate::config::Config;
+use crate::error::Error;
+use crate::metrics;
+use crate::password::{
+    check_password_breached, check_password_control_chars, check_password_length,
+    check_password_present, check_password_strength, check_pre_hashed, decode_password, with_context,
+};
+use crate::random_handler::generate_salt;
+use crate::server_timing::ServerTiming;
+use crate::types::{HashRequest, HashResponse, VerifyRequest, VerifyResponse};
+use crate::verify_handler::{check_algorithm, Algorithm};
+
+// ### Types
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct Pbkdf2HashOptions {
+    pub rounds: u32,
+    pub hash_function: Option<Pbkdf2HashFunction>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Pbkdf2HashFunction {
+    Sha256,
+    Sha512,
+}
+
+const PBKDF2_MIN_ROUNDS: u32 = 1000;
+
+// ### Functions
+pub async fn pbkdf2_hash_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let hash_req: HashRequest<Pbkdf2HashOptions> = parse_body(&mut req, config.max_body_bytes).await?;
+
+    check_pre_hashed(hash_req.pre_hashed, hash_req.min_score, hash_req.reject_breached)?;
+    let password = decode_password(&hash_req.password, hash_req.password_encoding, hash_req.pre_hashed)?;
+    check_password_present(&password, hash_req.allow_empty)?;
+    check_password_control_chars(&password, hash_req.reject_control_chars)?;
+    check_password_length(&password, config.max_password_bytes)?;
+    check_password_strength(&password, hash_req.min_score)?;
+    if hash_req.reject_breached {
+        check_password_breached(&password).await?;
+    }
+    let password = with_context(&password, hash_req.context.as_deref());
+
+    let pepper = config.peppers.current()?;
+    let password = pepper.apply(&password);
+    let password_hash = timing.measure(|| pbkdf2_hash(&password, hash_req.options, config.pbkdf2_max_rounds)).await?;
+    metrics::record_hash("pbkdf2");
+    let elapsed_ms = hash_req
+        .include_timing
+        .then(|| timing.elapsed_ms());
+
+    let hash_response = HashResponse {
+        hash: pepper.tag(password_hash),
+        elapsed_ms,
+    };
+    hash_response.to_json(config)
+}
+
+fn pbkdf2_hash(password: &[u8], options: Option<Pbkdf2HashOptions>, max_rounds: u32) -> Result<String, Error> {
+    let salt = generate_salt()?;
+
+    let (algorithm, params) = match options {
+        Some(opts) => {
+            if opts.rounds < PBKDF2_MIN_ROUNDS {
+                return Err(Error::InvalidHashOptions(format!(
+                    "rounds must be at least {PBKDF2_MIN_ROUNDS}"
+                )));
+            }
+
+            let algorithm = match opts.hash_function {
+                Some(Pbkdf2HashFunction::Sha512) => pbkdf2::Algorithm::Pbkdf2Sha512,
+                Some(Pbkdf2HashFunction::Sha256) | None => pbkdf2::Algorithm::Pbkdf2Sha256,
+            };
+            let params = pbkdf2::Params {
+                rounds: opts.rounds,
+                ..pbkdf2::Params::default()
+            };
+
+            (algorithm, params)
+        }
+
+        None => (pbkdf2::Algorithm::default(), pbkdf2::Params::default()),
+    };
+    check_pbkdf2_rounds(params.rounds, max_rounds)?;
+
+    Pbkdf2
+        .hash_password_customized(password, Some(algorithm.ident()), None, params, &salt)
+        .map(|password_hash| password_hash.to_string())
+        .map_err(|_err| Error::HashFailed)
+}
+
+pub async fn pbkdf2_verify_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let options: VerifyRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let password = decode_password(&options.password, options.password_encoding, options.pre_hashed)?;
+    let password = with_context(&password, options.context.as_deref());
+    let (pepper, hash) = config.peppers.for_hash(&options.hash)?;
+    let password = pepper.apply(&password);
+    let result = timing.measure(|| pbkdf2_verify(&password, hash, config.pbkdf2_max_rounds)).await?;
+    metrics::record_verify("pbkdf2", result);
+    let verify_response = VerifyResponse { result };
+    verify_response.to_json(config)
+}
+
+pub fn pbkdf2_verify(password: &[u8], hash: &str, max_rounds: u32) -> Result<bool, Error> {
+    check_algorithm(hash, Algorithm::Pbkdf2)?;
+    let password_hash = PasswordHash::new(hash)
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+
+    if !password_hash.algorithm.as_str().starts_with("pbkdf2") {
+        return Err(Error::InvalidPasswordHash);
+    }
+    let params = pbkdf2::Params::try_from(&password_hash)
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+    check_pbkdf2_rounds(params.rounds, max_rounds)?;
+
+    match Pbkdf2.verify_password(password, &password_hash) {
+        Ok(()) => Ok(true),
+
+        Err(err) => match err {
+            argon2::password_hash::Error::Password => Ok(false),
+            _ => Err(Error::VerifyFailed),
+        },
+    }
+}
+
+fn check_pbkdf2_rounds(rounds: u32, max_rounds: u32) -> Result<(), Error> {
+    if rounds > max_rounds {
+        return Err(Error::InvalidHashOptions(format!("rounds must be at most {max_rounds}")));
+    }
+
+    Ok(())
+}
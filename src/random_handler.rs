@@ -0,0 +1,193 @@
+//! Random tokens, passwords and salts.
+
+use argon2::password_hash::{Salt, SaltString};
+use argon2::password_hash::rand_core::OsRng;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use rand_core::RngCore;
+use worker::Request;
+
+use crate::body::parse_body;
+use crate::config::Config;
+use crate::encoding::{Encoding, encode};
+use crate::error::Error;
+
+// ### Types
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct RandomTokenQuery {
+    pub bytes: Option<usize>,
+    pub encoding: Option<Encoding>,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct RandomTokenResponse {
+    pub token: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct RandomPasswordRequest {
+    #[serde(default = "default_password_length")]
+    pub length: usize,
+    #[serde(default = "default_true")]
+    pub lowercase: bool,
+    #[serde(default = "default_true")]
+    pub uppercase: bool,
+    #[serde(default = "default_true")]
+    pub digits: bool,
+    #[serde(default = "default_true")]
+    pub symbols: bool,
+    #[serde(default)]
+    pub exclude: String,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct RandomPasswordResponse {
+    pub password: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct RandomSaltQuery {
+    pub encoding: Option<String>,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct RandomSaltResponse {
+    pub salt: String,
+}
+
+const DEFAULT_TOKEN_BYTES: usize = 32;
+pub const MAX_TOKEN_BYTES: usize = 256;
+
+const MAX_PASSWORD_LENGTH: usize = 256;
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+fn default_password_length() -> usize {
+    16
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// ### Functions
+pub fn random_token_handler(req: Request) -> Result<String, Error> {
+    let query: RandomTokenQuery = req
+        .query()
+        .map_err(|_err| Error::BadRequest)?;
+
+    let bytes = query.bytes.unwrap_or(DEFAULT_TOKEN_BYTES);
+    if !(1..=MAX_TOKEN_BYTES).contains(&bytes) {
+        return Err(Error::InvalidRandomLength);
+    }
+
+    let mut token = vec![0u8; bytes];
+    fill_random(&mut token)?;
+
+    let random_token_response = RandomTokenResponse {
+        token: encode(&token, query.encoding.unwrap_or(Encoding::Base64url))?,
+    };
+    serde_json::to_string(&random_token_response).map_err(|_err| Error::InternalServerError)
+}
+
+pub async fn random_password_handler(mut req: Request, config: &Config) -> Result<String, Error> {
+    let policy: RandomPasswordRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let password = random_password(&policy)?;
+
+    let random_password_response = RandomPasswordResponse { password };
+    serde_json::to_string(&random_password_response).map_err(|_err| Error::InternalServerError)
+}
+
+fn random_password(policy: &RandomPasswordRequest) -> Result<String, Error> {
+    let classes: Vec<Vec<char>> = [
+        (policy.lowercase, LOWERCASE),
+        (policy.uppercase, UPPERCASE),
+        (policy.digits, DIGITS),
+        (policy.symbols, SYMBOLS),
+    ]
+    .iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, class)| class.chars().filter(|c| !policy.exclude.contains(*c)).collect())
+    .collect();
+
+    if classes.is_empty() || classes.iter().any(|class| class.is_empty()) {
+        return Err(Error::InvalidPasswordPolicy);
+    }
+    if policy.length < classes.len() || policy.length > MAX_PASSWORD_LENGTH {
+        return Err(Error::InvalidPasswordPolicy);
+    }
+
+    let charset: Vec<char> = classes.concat();
+
+    // one character from each enabled class, then fill up from the whole charset
+    let mut password: Vec<char> = classes
+        .iter()
+        .map(|class| Ok(class[random_index(class.len())?]))
+        .collect::<Result<_, Error>>()?;
+    while password.len() < policy.length {
+        password.push(charset[random_index(charset.len())?]);
+    }
+
+    // Fisher-Yates, so the guaranteed characters don't always lead
+    for i in (1..password.len()).rev() {
+        password.swap(i, random_index(i + 1)?);
+    }
+
+    Ok(password.into_iter().collect())
+}
+
+pub fn random_salt_handler(req: Request) -> Result<String, Error> {
+    let query: RandomSaltQuery = req
+        .query()
+        .map_err(|_err| Error::BadRequest)?;
+
+    let salt = generate_salt()?;
+
+    let salt = match query.encoding.as_deref() {
+        // the PHC salt token, as embedded in `$argon2id$...$<salt>$<hash>`
+        Some("phc") | None => salt.as_str().to_string(),
+        // the raw salt bytes as standard, padded base64
+        Some("b64") => {
+            let mut buf = [0u8; Salt::MAX_LENGTH];
+            let bytes = salt.decode_b64(&mut buf).map_err(|_err| Error::InternalServerError)?;
+            BASE64.encode(bytes)
+        }
+        Some(_) => return Err(Error::InvalidEncoding),
+    };
+
+    let random_salt_response = RandomSaltResponse { salt };
+    serde_json::to_string(&random_salt_response).map_err(|_err| Error::InternalServerError)
+}
+
+// Uniform index in `0..bound`, rejecting samples past the largest multiple of
+// `bound` to avoid modulo bias.
+fn random_index(bound: usize) -> Result<usize, Error> {
+    let bound = bound as u32;
+    let limit = u32::MAX - u32::MAX % bound;
+
+    loop {
+        let mut sample = [0u8; 4];
+        fill_random(&mut sample)?;
+        let sample = u32::from_ne_bytes(sample);
+        if sample < limit {
+            return Ok((sample % bound) as usize);
+        }
+    }
+}
+
+// The platform RNG failing is an incident on our side, not a bad request, so
+// it gets its own error instead of the panic in `OsRng`'s infallible methods.
+pub fn fill_random(bytes: &mut [u8]) -> Result<(), Error> {
+    OsRng
+        .try_fill_bytes(bytes)
+        .map_err(|_err| Error::RandomUnavailable)
+}
+
+pub fn generate_salt() -> Result<SaltString, Error> {
+    let mut bytes = [0u8; Salt::RECOMMENDED_LENGTH];
+    fill_random(&mut bytes)?;
+
+    SaltString::encode_b64(&bytes).map_err(|_err| Error::InternalServerError)
+}
@@ -0,0 +1,51 @@
+//! Fixed one minute windows per `CF-Connecting-IP`, counted in the `RATE_LIMIT`
+//! KV namespace. KV is eventually consistent, so bursts across colos may
+//! slightly exceed the limit.
+
+use worker::{Date, Env, Request};
+
+use crate::config::Config;
+use crate::error::Error;
+
+// The limit only sheds load, so a KV outage lets requests through rather than
+// failing every one of them.
+pub async fn check_rate_limit(req: &Request, env: &Env, config: &Config) -> Result<(), Error> {
+    let kv = match env.kv("RATE_LIMIT") {
+        Ok(kv) => kv,
+        Err(_err) => return Ok(()),
+    };
+
+    let ip = match req.headers().get("CF-Connecting-IP") {
+        Ok(Some(ip)) => ip,
+        _ => return Ok(()),
+    };
+
+    let now = Date::now().as_millis() / 1000;
+    let window = now / 60;
+    let key = format!("rate-limit:{ip}:{window}");
+
+    let count: u64 = match kv.get(&key).text().await {
+        Ok(count) => count.and_then(|count| count.parse().ok()).unwrap_or(0),
+        Err(err) => {
+            worker::console_error!("rate limit: reading the count failed: {err}");
+            return Ok(());
+        }
+    };
+
+    if count >= config.rate_limit_per_minute {
+        return Err(Error::RateLimited {
+            retry_after: (window + 1) * 60 - now,
+        });
+    }
+
+    let put = match kv.put(&key, (count + 1).to_string()) {
+        // KV's minimum TTL is 60 seconds
+        Ok(put) => put.expiration_ttl(120).execute().await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = put {
+        worker::console_error!("rate limit: writing the count failed: {err}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,473 @@
+//! Route table and dispatch.
+
+use worker::{Env, Headers, Method, Request, Response};
+
+use crate::argon2_handler::{
+    argon2_benchmark_handler, argon2_default_params_handler, argon2_derive_key_handler, argon2_estimate_handler,
+    argon2_hash_batch_handler, argon2_hash_handler, argon2_hash_raw_handler, argon2_hash_text_handler, argon2_needs_rehash_handler,
+    argon2_verify_and_advise_handler, argon2_verify_safe_handler, argon2_warmup_handler, argon2id_verify_handler,
+};
+use crate::balloon_handler::{balloon_hash_handler, balloon_verify_handler};
+use crate::bcrypt_handler::{
+    bcrypt_default_params_handler, bcrypt_hash_handler, bcrypt_needs_rehash_handler,
+    bcrypt_verify_handler,
+};
+use crate::concurrency::acquire_hash_permit;
+use crate::config::Config;
+use crate::digest_handler::{blake3_handler, digest_handler, digest_stream_handler};
+use crate::encoding::{Encoding, convert_handler};
+use crate::ed25519_handler::{ed25519_sign_handler, ed25519_verify_handler};
+use crate::encrypt_handler::{Cipher, decrypt_handler, encrypt_handler, reencrypt_handler};
+use crate::error::Error;
+use crate::hash_handler::{dual_hash_handler, hash_handler};
+use crate::health_handler::health_handler;
+use crate::hmac_handler::{hmac_handler, hmac_verify_handler};
+use crate::inspect_handler::{inspect_handler, validate_hash_handler};
+use crate::jwt_handler::{jwt_sign_handler, jwt_verify_handler};
+use crate::metrics::{self, inflight_handler, metrics_handler, METRICS_CONTENT_TYPE};
+use crate::nonce_handler::{nonce_handler, nonce_verify_handler};
+use crate::openapi_handler::openapi_handler;
+use crate::password::{password_breached_handler, password_strength_handler};
+use crate::pbkdf2_handler::{pbkdf2_hash_handler, pbkdf2_verify_handler};
+use crate::random_handler::{random_password_handler, random_salt_handler, random_token_handler};
+use crate::scrypt_handler::{scrypt_hash_handler, scrypt_verify_handler};
+use crate::server_timing::ServerTiming;
+use crate::totp_handler::{totp_generate_handler, totp_verify_handler};
+use crate::verify_handler::{verify_batch_handler, verify_handler, verify_multi_handler};
+use crate::version_handler::version_handler;
+
+// HEAD is served by the GET route for the path, without the body.
+pub async fn dispatch(req: Request, env: &Env, config: &Config) -> Result<Response, Error> {
+    if req.method() == Method::Head {
+        let response = dispatch_method(req, Method::Get, env, config).await?;
+        return Response::empty()
+            .map(|empty| {
+                empty
+                    .with_status(response.status_code())
+                    .with_headers(response.headers().clone())
+            })
+            .map_err(|_err| Error::InternalServerError);
+    }
+
+    let method = req.method();
+    dispatch_method(req, method, env, config).await
+}
+
+// Handlers return a JSON body; the few that don't return their own `Response`.
+async fn dispatch_method(req: Request, method: Method, env: &Env, config: &Config) -> Result<Response, Error> {
+    let path = routed_path(&req.path(), config)?;
+    let entry = resolve_route(&method, &path, config)?;
+
+    metrics::record_request(entry.path);
+    let hashes = hashes_passwords(entry.route);
+    let _in_flight = hashes.then(metrics::track_in_flight);
+    let _hash_permit = hashes
+        .then(|| acquire_hash_permit(config.max_concurrent_hashes))
+        .transpose()?;
+
+    let timing = ServerTiming::default();
+    let body = match entry.route {
+        Route::Metrics => {
+            let body = metrics_handler()?;
+            let headers = response_headers(METRICS_CONTENT_TYPE, entry.route, &timing, config)?;
+            return Response::ok(body)
+                .map(|response| response.with_headers(headers))
+                .map_err(|_err| Error::InternalServerError);
+        }
+        Route::Argon2Hash if accepts_octet_stream(&req)? => {
+            let body = argon2_hash_raw_handler(req, config, &timing).await?;
+            let headers = response_headers("application/octet-stream", entry.route, &timing, config)?;
+            return Response::from_bytes(body)
+                .map(|response| response.with_headers(headers))
+                .map_err(|_err| Error::InternalServerError);
+        }
+        Route::Argon2Hash => argon2_hash_handler(req, config, &timing).await,
+        Route::Argon2HashText => argon2_hash_text_handler(req, config, &timing).await,
+        Route::Argon2HashBatch => argon2_hash_batch_handler(req, env, config, &timing).await,
+        Route::Argon2Verify => argon2id_verify_handler(req, config, &timing).await,
+        Route::Argon2VerifySafe => argon2_verify_safe_handler(req, config, &timing).await,
+        Route::Argon2NeedsRehash => argon2_needs_rehash_handler(req, config).await,
+        Route::Argon2Estimate => argon2_estimate_handler(req, config).await,
+        Route::Argon2Benchmark => argon2_benchmark_handler(req, config).await,
+        Route::Argon2DeriveKey => argon2_derive_key_handler(req, config, &timing).await,
+        Route::Argon2VerifyAndAdvise => argon2_verify_and_advise_handler(req, config, &timing).await,
+        Route::Argon2DefaultParams => argon2_default_params_handler(config),
+        Route::Argon2Warmup => argon2_warmup_handler(config, &timing).await,
+        Route::BcryptHash => bcrypt_hash_handler(req, config, &timing, false).await,
+        Route::BcryptVerify => bcrypt_verify_handler(req, config, &timing, false).await,
+        Route::BcryptNeedsRehash => bcrypt_needs_rehash_handler(req, config).await,
+        Route::BcryptDefaultParams => bcrypt_default_params_handler(config),
+        Route::BcryptSha256Hash => bcrypt_hash_handler(req, config, &timing, true).await,
+        Route::BcryptSha256Verify => bcrypt_verify_handler(req, config, &timing, true).await,
+        Route::ScryptHash => scrypt_hash_handler(req, config, &timing).await,
+        Route::ScryptVerify => scrypt_verify_handler(req, config, &timing).await,
+        Route::Pbkdf2Hash => pbkdf2_hash_handler(req, config, &timing).await,
+        Route::Pbkdf2Verify => pbkdf2_verify_handler(req, config, &timing).await,
+        Route::BalloonHash => balloon_hash_handler(req, config, &timing).await,
+        Route::BalloonVerify => balloon_verify_handler(req, config, &timing).await,
+        Route::Hash => hash_handler(req, config, &timing).await,
+        Route::HashDual => dual_hash_handler(req, config, &timing).await,
+        Route::Verify => verify_handler(req, config, &timing).await,
+        Route::VerifyMulti => verify_multi_handler(req, config, &timing).await,
+        Route::VerifyBatch => verify_batch_handler(req, config, &timing).await,
+        Route::Inspect => inspect_handler(req, config).await,
+        Route::ValidateHash => validate_hash_handler(req, config).await,
+        Route::Hmac => hmac_handler(req, config).await,
+        Route::HmacVerify => hmac_verify_handler(req, config).await,
+        Route::Encrypt => encrypt_handler(req, config, Cipher::Aes256Gcm).await,
+        Route::Decrypt => decrypt_handler(req, config, Cipher::Aes256Gcm).await,
+        Route::EncryptChaCha20Poly1305 => encrypt_handler(req, config, Cipher::ChaCha20Poly1305).await,
+        Route::DecryptChaCha20Poly1305 => decrypt_handler(req, config, Cipher::ChaCha20Poly1305).await,
+        Route::EncryptXChaCha20Poly1305 => encrypt_handler(req, config, Cipher::XChaCha20Poly1305).await,
+        Route::DecryptXChaCha20Poly1305 => decrypt_handler(req, config, Cipher::XChaCha20Poly1305).await,
+        Route::Reencrypt => reencrypt_handler(req, config, Cipher::Aes256Gcm).await,
+        Route::Digest => digest_handler(req, config).await,
+        Route::DigestStream => digest_stream_handler(req).await,
+        Route::Blake3 => blake3_handler(req, config).await,
+        Route::Encode => convert_handler(req, config, Encoding::Utf8, Encoding::Base64).await,
+        Route::Decode => convert_handler(req, config, Encoding::Base64, Encoding::Utf8).await,
+        Route::JwtSign => jwt_sign_handler(req, config).await,
+        Route::JwtVerify => jwt_verify_handler(req, config).await,
+        Route::Ed25519Sign => ed25519_sign_handler(req, config).await,
+        Route::Ed25519Verify => ed25519_verify_handler(req, config).await,
+        Route::TotpGenerate => totp_generate_handler(req, config).await,
+        Route::TotpVerify => totp_verify_handler(req, config).await,
+        Route::RandomToken => random_token_handler(req),
+        Route::RandomPassword => random_password_handler(req, config).await,
+        Route::RandomSalt => random_salt_handler(req),
+        Route::Nonce => nonce_handler(config),
+        Route::NonceVerify => nonce_verify_handler(req, config).await,
+        Route::PasswordStrength => password_strength_handler(req, config).await,
+        Route::PasswordBreached => password_breached_handler(req, config).await,
+        Route::Health => health_handler(config),
+        Route::Version => version_handler(),
+        Route::OpenApi => openapi_handler(),
+        Route::InFlight => inflight_handler(),
+    }?;
+
+    let headers = response_headers("application/json", entry.route, &timing, config)?;
+    Response::ok(body)
+        .map(|response| response.with_headers(headers))
+        .map_err(|_err| Error::InternalServerError)
+}
+
+fn response_headers(content_type: &str, route: Route, timing: &ServerTiming, config: &Config) -> Result<Headers, Error> {
+    let mut headers = Headers::new();
+    headers
+        .set("Content-Type", content_type)
+        .map_err(|_err| Error::InternalServerError)?;
+    headers
+        .set("Cache-Control", &cache_control(route, config))
+        .map_err(|_err| Error::InternalServerError)?;
+    if let Some(server_timing) = timing.header_value().filter(|_| config.server_timing) {
+        headers
+            .set("Server-Timing", &server_timing)
+            .map_err(|_err| Error::InternalServerError)?;
+    }
+
+    Ok(headers)
+}
+
+// The routes that run a password hash or KDF, which `MAX_CONCURRENT_HASHES`
+// limits and the in-flight gauge counts; the cheap ones next to them, such as
+// `/argon2/estimate`, are neither.
+fn hashes_passwords(route: Route) -> bool {
+    matches!(
+        route,
+        Route::Argon2Hash
+            | Route::Argon2HashText
+            | Route::Argon2HashBatch
+            | Route::Argon2Verify
+            | Route::Argon2VerifySafe
+            | Route::Argon2Benchmark
+            | Route::Argon2DeriveKey
+            | Route::Argon2VerifyAndAdvise
+            | Route::Argon2Warmup
+            | Route::BcryptHash
+            | Route::BcryptVerify
+            | Route::BcryptSha256Hash
+            | Route::BcryptSha256Verify
+            | Route::ScryptHash
+            | Route::ScryptVerify
+            | Route::Pbkdf2Hash
+            | Route::Pbkdf2Verify
+            | Route::BalloonHash
+            | Route::BalloonVerify
+            | Route::Hash
+            | Route::HashDual
+            | Route::Verify
+            | Route::VerifyMulti
+            | Route::VerifyBatch
+    )
+}
+
+// Only responses that depend on the deployment alone may be cached; anything
+// else could carry hashes, keys or verification results.
+fn cache_control(route: Route, config: &Config) -> String {
+    match route {
+        Route::Health | Route::Version | Route::Argon2DefaultParams | Route::BcryptDefaultParams | Route::OpenApi => {
+            format!("public, max-age={}", config.readonly_cache_seconds)
+        }
+        _ => "no-store".into(),
+    }
+}
+
+// Only an explicit `application/octet-stream` counts; `*/*` keeps JSON.
+fn accepts_octet_stream(req: &Request) -> Result<bool, Error> {
+    let accept = req
+        .headers()
+        .get("Accept")
+        .map_err(|_err| Error::BadRequest)?
+        .unwrap_or_default();
+
+    Ok(accept
+        .split(',')
+        .filter_map(|media_range| media_range.split(';').next())
+        .any(|media_type| media_type.trim().eq_ignore_ascii_case("application/octet-stream")))
+}
+
+#[derive(Clone, Copy)]
+pub enum Route {
+    Argon2Hash,
+    Argon2HashText,
+    Argon2HashBatch,
+    Argon2Verify,
+    Argon2VerifySafe,
+    Argon2NeedsRehash,
+    Argon2Estimate,
+    Argon2Benchmark,
+    Argon2DeriveKey,
+    Argon2VerifyAndAdvise,
+    Argon2DefaultParams,
+    Argon2Warmup,
+    BcryptHash,
+    BcryptVerify,
+    BcryptNeedsRehash,
+    BcryptDefaultParams,
+    BcryptSha256Hash,
+    BcryptSha256Verify,
+    ScryptHash,
+    ScryptVerify,
+    Pbkdf2Hash,
+    Pbkdf2Verify,
+    BalloonHash,
+    BalloonVerify,
+    Hash,
+    HashDual,
+    Verify,
+    VerifyMulti,
+    VerifyBatch,
+    Inspect,
+    ValidateHash,
+    Hmac,
+    HmacVerify,
+    Encrypt,
+    Decrypt,
+    EncryptChaCha20Poly1305,
+    DecryptChaCha20Poly1305,
+    EncryptXChaCha20Poly1305,
+    DecryptXChaCha20Poly1305,
+    Reencrypt,
+    Digest,
+    DigestStream,
+    Blake3,
+    Encode,
+    Decode,
+    JwtSign,
+    JwtVerify,
+    Ed25519Sign,
+    Ed25519Verify,
+    TotpGenerate,
+    TotpVerify,
+    RandomToken,
+    RandomPassword,
+    RandomSalt,
+    Nonce,
+    NonceVerify,
+    PasswordStrength,
+    PasswordBreached,
+    Health,
+    Version,
+    OpenApi,
+    InFlight,
+    Metrics,
+}
+
+pub struct RouteEntry {
+    pub method: Method,
+    pub path: &'static str,
+    // the password hashing algorithm served by this route, if any
+    pub algorithm: Option<&'static str>,
+    pub route: Route,
+}
+
+// The path routes are matched against: normalized, with `ROUTE_PREFIX` removed.
+pub fn routed_path(path: &str, config: &Config) -> Result<String, Error> {
+    let path = normalize_path(path);
+    strip_route_prefix(&path, config.route_prefix.as_deref()).map(str::to_owned)
+}
+
+// The route a `routed_path` is served by, resolved as `dispatch` does but
+// without checking whether it is enabled; for the request log.
+pub fn request_route(method: &Method, path: &str) -> Option<&'static RouteEntry> {
+    let method = if *method == Method::Head { &Method::Get } else { method };
+    find_route(method, path)
+}
+
+pub fn find_route(method: &Method, path: &str) -> Option<&'static RouteEntry> {
+    ROUTES
+        .iter()
+        .find(|entry| entry.method == *method && entry.path == path)
+}
+
+// A known path requested with the wrong method is a 405 listing the methods it
+// does accept; only unknown paths are a 404. Routes of algorithms left out of
+// `ENABLED_ALGORITHMS` don't exist at all.
+fn resolve_route(method: &Method, path: &str, config: &Config) -> Result<&'static RouteEntry, Error> {
+    if let Some(entry) = find_route(method, path).filter(|entry| route_enabled(entry, config)) {
+        return Ok(entry);
+    }
+
+    let mut allowed: Vec<&str> = ROUTES
+        .iter()
+        .filter(|entry| entry.path == path && route_enabled(entry, config))
+        .map(|entry| entry.method.as_ref())
+        .collect();
+    if allowed.contains(&"GET") {
+        allowed.push("HEAD");
+    }
+
+    if allowed.is_empty() {
+        return Err(Error::InvalidRoute);
+    }
+
+    Err(Error::MethodNotAllowed { allow: allowed.join(", ") })
+}
+
+pub fn route_enabled(entry: &RouteEntry, config: &Config) -> bool {
+    entry
+        .algorithm
+        .is_none_or(|algorithm| config.algorithm_enabled(algorithm))
+}
+
+// Gateways sometimes append a slash or double one up, so empty segments are
+// dropped: `/argon2//hash/` is served as `/argon2/hash`.
+fn normalize_path(path: &str) -> String {
+    let mut normalized = String::with_capacity(path.len());
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        normalized.push('/');
+        normalized.push_str(segment);
+    }
+    if normalized.is_empty() {
+        normalized.push('/');
+    }
+
+    normalized
+}
+
+// With `ROUTE_PREFIX` set every route, `/health` and `/version` included, is
+// only served under it; the prefix must end at a segment boundary.
+fn strip_route_prefix<'path>(path: &'path str, prefix: Option<&str>) -> Result<&'path str, Error> {
+    let Some(prefix) = prefix else {
+        return Ok(path);
+    };
+
+    match path.strip_prefix(prefix) {
+        Some(rest) if rest.starts_with('/') => Ok(rest),
+        _ => Err(Error::InvalidRoute),
+    }
+}
+
+const fn route(method: Method, path: &'static str, algorithm: Option<&'static str>, route: Route) -> RouteEntry {
+    RouteEntry {
+        method,
+        path,
+        algorithm,
+        route,
+    }
+}
+
+pub const ROUTES: &[RouteEntry] = &[
+    // argon2 defaults to argon2id, see `Argon2Variant`
+    route(Method::Post, "/argon2/hash", Some("argon2"), Route::Argon2Hash),
+    route(Method::Post, "/argon2/hash/text", Some("argon2"), Route::Argon2HashText),
+    route(Method::Post, "/argon2/hash/batch", Some("argon2"), Route::Argon2HashBatch),
+    route(Method::Post, "/argon2/verify", Some("argon2"), Route::Argon2Verify),
+    route(Method::Post, "/argon2/verify-safe", Some("argon2"), Route::Argon2VerifySafe),
+    route(Method::Post, "/argon2/needs-rehash", Some("argon2"), Route::Argon2NeedsRehash),
+    route(Method::Post, "/argon2/estimate", Some("argon2"), Route::Argon2Estimate),
+    route(Method::Post, "/argon2/benchmark", Some("argon2"), Route::Argon2Benchmark),
+    route(Method::Post, "/argon2/derive-key", Some("argon2"), Route::Argon2DeriveKey),
+    route(Method::Post, "/argon2/verify-and-advise", Some("argon2"), Route::Argon2VerifyAndAdvise),
+    route(Method::Get, "/argon2/params/default", Some("argon2"), Route::Argon2DefaultParams),
+    route(Method::Get, "/warmup", Some("argon2"), Route::Argon2Warmup),
+    route(Method::Post, "/bcrypt/hash", Some("bcrypt"), Route::BcryptHash),
+    route(Method::Post, "/bcrypt/verify", Some("bcrypt"), Route::BcryptVerify),
+    route(Method::Post, "/bcrypt/needs-rehash", Some("bcrypt"), Route::BcryptNeedsRehash),
+    route(Method::Get, "/bcrypt/params/default", Some("bcrypt"), Route::BcryptDefaultParams),
+    route(Method::Post, "/bcrypt-sha256/hash", Some("bcrypt-sha256"), Route::BcryptSha256Hash),
+    route(Method::Post, "/bcrypt-sha256/verify", Some("bcrypt-sha256"), Route::BcryptSha256Verify),
+    route(Method::Post, "/scrypt/hash", Some("scrypt"), Route::ScryptHash),
+    route(Method::Post, "/scrypt/verify", Some("scrypt"), Route::ScryptVerify),
+    route(Method::Post, "/pbkdf2/hash", Some("pbkdf2"), Route::Pbkdf2Hash),
+    route(Method::Post, "/pbkdf2/verify", Some("pbkdf2"), Route::Pbkdf2Verify),
+    route(Method::Post, "/balloon/hash", Some("balloon"), Route::BalloonHash),
+    route(Method::Post, "/balloon/verify", Some("balloon"), Route::BalloonVerify),
+    route(Method::Post, "/hash", None, Route::Hash),
+    route(Method::Post, "/hash/dual", None, Route::HashDual),
+    route(Method::Post, "/verify", None, Route::Verify),
+    route(Method::Post, "/verify/multi", None, Route::VerifyMulti),
+    route(Method::Post, "/verify/batch", None, Route::VerifyBatch),
+    route(Method::Post, "/inspect", None, Route::Inspect),
+    route(Method::Post, "/validate-hash", None, Route::ValidateHash),
+    route(Method::Post, "/hmac", None, Route::Hmac),
+    route(Method::Post, "/hmac/verify", None, Route::HmacVerify),
+    route(Method::Post, "/encrypt", None, Route::Encrypt),
+    route(Method::Post, "/decrypt", None, Route::Decrypt),
+    route(Method::Post, "/encrypt/chacha20poly1305", None, Route::EncryptChaCha20Poly1305),
+    route(Method::Post, "/decrypt/chacha20poly1305", None, Route::DecryptChaCha20Poly1305),
+    route(Method::Post, "/encrypt/xchacha20poly1305", None, Route::EncryptXChaCha20Poly1305),
+    route(Method::Post, "/decrypt/xchacha20poly1305", None, Route::DecryptXChaCha20Poly1305),
+    route(Method::Post, "/reencrypt", None, Route::Reencrypt),
+    route(Method::Post, "/digest", None, Route::Digest),
+    route(Method::Post, "/digest/stream", None, Route::DigestStream),
+    route(Method::Post, "/blake3", None, Route::Blake3),
+    route(Method::Post, "/encode", None, Route::Encode),
+    route(Method::Post, "/decode", None, Route::Decode),
+    route(Method::Post, "/jwt/sign", None, Route::JwtSign),
+    route(Method::Post, "/jwt/verify", None, Route::JwtVerify),
+    route(Method::Post, "/ed25519/sign", None, Route::Ed25519Sign),
+    route(Method::Post, "/ed25519/verify", None, Route::Ed25519Verify),
+    route(Method::Post, "/totp/generate", None, Route::TotpGenerate),
+    route(Method::Post, "/totp/verify", None, Route::TotpVerify),
+    route(Method::Get, "/random/token", None, Route::RandomToken),
+    route(Method::Post, "/random/password", None, Route::RandomPassword),
+    route(Method::Get, "/random/salt", None, Route::RandomSalt),
+    route(Method::Get, "/nonce", None, Route::Nonce),
+    route(Method::Post, "/nonce/verify", None, Route::NonceVerify),
+    route(Method::Post, "/password/strength", None, Route::PasswordStrength),
+    route(Method::Post, "/password/breached", None, Route::PasswordBreached),
+    route(Method::Get, "/health", None, Route::Health),
+    route(Method::Get, "/version", None, Route::Version),
+    route(Method::Get, "/openapi.json", None, Route::OpenApi),
+    route(Method::Get, "/metrics", None, Route::Metrics),
+    route(Method::Get, "/inflight", None, Route::InFlight),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailing_and_repeated_slashes_are_dropped() {
+        assert_eq!(normalize_path("/argon2/hash/"), "/argon2/hash");
+        assert_eq!(normalize_path("//argon2//hash"), "/argon2/hash");
+        assert_eq!(normalize_path("/argon2/hash"), "/argon2/hash");
+    }
+
+    #[test]
+    fn the_root_stays_a_single_slash() {
+        assert_eq!(normalize_path("/"), "/");
+        assert_eq!(normalize_path("//"), "/");
+    }
+}
@@ -0,0 +1,139 @@
+//! scrypt hashing and verification.
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier};
+use scrypt::Scrypt;
+use worker::Request;
+
+use crate::body::parse_body;
+use crate::config::{Config, ScryptLimits};
+use crate::error::Error;
+use crate::metrics;
+use crate::password::{
+    check_password_breached, check_password_control_chars, check_password_length,
+    check_password_present, check_password_strength, check_pre_hashed, decode_password, with_context,
+};
+use crate::random_handler::generate_salt;
+use crate::server_timing::ServerTiming;
+use crate::types::{HashRequest, HashResponse, VerifyRequest, VerifyResponse};
+use crate::verify_handler::{check_algorithm, Algorithm};
+
+// ### Types
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct ScryptHashOptions {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+// OWASP's 32 MiB alternative to the crate's recommended 128 MiB, which would
+// take the whole isolate: N = 2^15, r = 8, p = 3.
+const SCRYPT_DEFAULT_LOG_N: u8 = 15;
+const SCRYPT_DEFAULT_R: u32 = 8;
+const SCRYPT_DEFAULT_P: u32 = 3;
+
+// ### Functions
+pub async fn scrypt_hash_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let hash_req: HashRequest<ScryptHashOptions> = parse_body(&mut req, config.max_body_bytes).await?;
+
+    check_pre_hashed(hash_req.pre_hashed, hash_req.min_score, hash_req.reject_breached)?;
+    let password = decode_password(&hash_req.password, hash_req.password_encoding, hash_req.pre_hashed)?;
+    check_password_present(&password, hash_req.allow_empty)?;
+    check_password_control_chars(&password, hash_req.reject_control_chars)?;
+    check_password_length(&password, config.max_password_bytes)?;
+    check_password_strength(&password, hash_req.min_score)?;
+    if hash_req.reject_breached {
+        check_password_breached(&password).await?;
+    }
+    let password = with_context(&password, hash_req.context.as_deref());
+
+    let pepper = config.peppers.current()?;
+    let password = pepper.apply(&password);
+    let password_hash = timing.measure(|| scrypt_hash(&password, hash_req.options, &config.scrypt_limits)).await?;
+    metrics::record_hash("scrypt");
+    let elapsed_ms = hash_req
+        .include_timing
+        .then(|| timing.elapsed_ms());
+
+    let hash_response = HashResponse {
+        hash: pepper.tag(password_hash),
+        elapsed_ms,
+    };
+    hash_response.to_json(config)
+}
+
+pub fn scrypt_hash(password: &[u8], options: Option<ScryptHashOptions>, limits: &ScryptLimits) -> Result<String, Error> {
+    let salt = generate_salt()?;
+
+    let params = match options {
+        Some(opts) => scrypt::Params::new(
+            opts.log_n,
+            opts.r,
+            opts.p,
+            scrypt::Params::RECOMMENDED_LEN,
+        ).map_err(|err| Error::InvalidHashOptions(err.to_string())),
+
+        None => scrypt::Params::new(
+            SCRYPT_DEFAULT_LOG_N,
+            SCRYPT_DEFAULT_R,
+            SCRYPT_DEFAULT_P,
+            scrypt::Params::RECOMMENDED_LEN,
+        ).map_err(|_err| Error::HashFailed),
+    }?;
+    check_scrypt_limits(&params, limits)?;
+
+    Scrypt
+        .hash_password_customized(password, None, None, params, &salt)
+        .map(|password_hash| password_hash.to_string())
+        .map_err(|_err| Error::HashFailed)
+}
+
+pub async fn scrypt_verify_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let options: VerifyRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let password = decode_password(&options.password, options.password_encoding, options.pre_hashed)?;
+    let password = with_context(&password, options.context.as_deref());
+    let (pepper, hash) = config.peppers.for_hash(&options.hash)?;
+    let password = pepper.apply(&password);
+    let result = timing.measure(|| scrypt_verify(&password, hash, &config.scrypt_limits)).await?;
+    metrics::record_verify("scrypt", result);
+    let verify_response = VerifyResponse { result };
+    verify_response.to_json(config)
+}
+
+pub fn scrypt_verify(password: &[u8], hash: &str, limits: &ScryptLimits) -> Result<bool, Error> {
+    check_algorithm(hash, Algorithm::Scrypt)?;
+    let password_hash = PasswordHash::new(hash)
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+    let params = scrypt::Params::try_from(&password_hash)
+        .map_err(|_err| Error::InvalidPasswordHash)?;
+    check_scrypt_limits(&params, limits)?;
+
+    match Scrypt.verify_password(password, &password_hash) {
+        Ok(()) => Ok(true),
+
+        Err(err) => match err {
+            argon2::password_hash::Error::Password => Ok(false),
+            _ => Err(Error::VerifyFailed),
+        },
+    }
+}
+
+fn check_scrypt_limits(params: &scrypt::Params, limits: &ScryptLimits) -> Result<(), Error> {
+    // 128 * r * 2^log_n bytes; log_n is below 64, so this can't overflow.
+    let memory_kib = (u128::from(params.r()) << params.log_n()) / 8;
+    if memory_kib > u128::from(limits.max_memory_kib) {
+        return Err(Error::InvalidHashOptions(format!(
+            "128 * r * 2^log_n must be at most {} KiB",
+            limits.max_memory_kib
+        )));
+    }
+    if params.p() > limits.max_p {
+        return Err(Error::InvalidHashOptions(format!(
+            "p must be at most {}",
+            limits.max_p
+        )));
+    }
+
+    Ok(())
+}
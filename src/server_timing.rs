@@ -0,0 +1,36 @@
+//! The `Server-Timing` header on hash and verify responses.
+
+use std::cell::Cell;
+use std::time::Duration;
+
+use worker::{Date, Delay};
+
+// Created per request by `dispatch` and handed to the handlers that hash or
+// verify; only the crypto calls themselves are measured.
+#[derive(Default)]
+pub struct ServerTiming {
+    hash_ms: Cell<Option<u64>>,
+}
+
+impl ServerTiming {
+    // Repeated measurements add up, so a batch reports its total. The Workers
+    // clock only advances across I/O, so a zero delay on either side of `f`
+    // brings it up to date, as `/argon2/benchmark` does.
+    pub async fn measure<T>(&self, f: impl FnOnce() -> T) -> T {
+        Delay::from(Duration::ZERO).await;
+        let started = Date::now().as_millis();
+        let result = f();
+        Delay::from(Duration::ZERO).await;
+        let elapsed = Date::now().as_millis() - started;
+        self.hash_ms.set(Some(self.elapsed_ms() + elapsed));
+        result
+    }
+
+    pub fn elapsed_ms(&self) -> u64 {
+        self.hash_ms.get().unwrap_or_default()
+    }
+
+    pub fn header_value(&self) -> Option<String> {
+        self.hash_ms.get().map(|ms| format!("hash;dur={ms}"))
+    }
+}
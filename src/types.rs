@@ -0,0 +1,132 @@
+//! Request and response types shared by the password hashing algorithms.
+
+use crate::config::Config;
+use crate::encoding::Encoding;
+use crate::error::Error;
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct HashRequest<T> {
+    pub password: String,
+    #[serde(default)]
+    pub password_encoding: Encoding,
+    pub options: Option<T>,
+    /// Hashed as `context || 0x00 || password`; must be the same on verify.
+    pub context: Option<String>,
+    /// `password` is its SHA-256 digest, see `password::decode_password`.
+    #[serde(default)]
+    pub pre_hashed: bool,
+    #[serde(default)]
+    pub include_timing: bool,
+    pub min_score: Option<u8>,
+    #[serde(default)]
+    pub reject_breached: bool,
+    #[serde(default)]
+    pub allow_empty: bool,
+    #[serde(default)]
+    pub reject_control_chars: bool,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct HashResponse {
+    pub hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<u64>,
+}
+
+// With `LEGACY_FIELD_NAMES`, for clients that predate the current names.
+#[derive(serde::Serialize)]
+struct LegacyHashResponse<'a> {
+    digest: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    elapsed_ms: Option<u64>,
+}
+
+impl HashResponse {
+    pub fn to_json(&self, config: &Config) -> Result<String, Error> {
+        let json = if config.legacy_field_names {
+            serde_json::to_string(&LegacyHashResponse {
+                digest: &self.hash,
+                elapsed_ms: self.elapsed_ms,
+            })
+        } else {
+            serde_json::to_string(self)
+        };
+        json.map_err(|_err| Error::InternalServerError)
+    }
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BatchHashRequest<T> {
+    pub passwords: Vec<String>,
+    pub options: Option<T>,
+    /// Hashed as `context || 0x00 || password`; must be the same on verify.
+    pub context: Option<String>,
+    #[serde(default)]
+    pub allow_empty: bool,
+    #[serde(default)]
+    pub reject_control_chars: bool,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct BatchHashResponse {
+    pub results: Vec<BatchHashResult>,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+#[serde(untagged)]
+pub enum BatchHashResult {
+    Hash { hash: String },
+    Error { error: String },
+}
+
+pub const MAX_BATCH_SIZE: usize = 100;
+
+// ## Verify
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct VerifyRequest {
+    pub password: String,
+    #[serde(default)]
+    pub password_encoding: Encoding,
+    pub hash: String,
+    /// The `context` the hash was made with, if any.
+    pub context: Option<String>,
+    /// Whether the hash was made `pre_hashed`.
+    #[serde(default)]
+    pub pre_hashed: bool,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct VerifyResponse {
+    pub result: bool,
+}
+
+#[derive(serde::Serialize)]
+struct LegacyVerifyResponse {
+    ok: bool,
+}
+
+impl VerifyResponse {
+    pub fn to_json(&self, config: &Config) -> Result<String, Error> {
+        let json = if config.legacy_field_names {
+            serde_json::to_string(&LegacyVerifyResponse { ok: self.result })
+        } else {
+            serde_json::to_string(self)
+        };
+        json.map_err(|_err| Error::InternalServerError)
+    }
+}
+
+// ## Rehash
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct NeedsRehashRequest<T> {
+    pub hash: String,
+    pub options: Option<T>,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct NeedsRehashResponse {
+    pub needs_rehash: bool,
+}
@@ -0,0 +1,272 @@
+//! Verification against any supported hash, detecting the algorithm from its prefix.
+
+use worker::Request;
+
+use crate::argon2_handler::argon2id_verify;
+use crate::balloon_handler::balloon_verify;
+use crate::bcrypt_handler::bcrypt_verify;
+use crate::body::parse_body;
+use crate::config::Config;
+use crate::encoding::Encoding;
+use crate::error::Error;
+use crate::metrics;
+use crate::password::{decode_password, with_context};
+use crate::pbkdf2_handler::pbkdf2_verify;
+use crate::scrypt_handler::scrypt_verify;
+use crate::server_timing::ServerTiming;
+use crate::types::{MAX_BATCH_SIZE, VerifyRequest, VerifyResponse};
+
+// ### Types
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MultiVerifyRequest {
+    pub password: String,
+    #[serde(default)]
+    pub password_encoding: Encoding,
+    pub hashes: Vec<String>,
+    /// The `context` the hash was made with, if any.
+    pub context: Option<String>,
+    /// Whether the hashes were made `pre_hashed`.
+    #[serde(default)]
+    pub pre_hashed: bool,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct MultiVerifyResponse {
+    pub result: bool,
+    pub matched_index: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<MultiVerifyError>,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct MultiVerifyError {
+    pub index: usize,
+    pub error: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BatchVerifyRequest {
+    pub items: Vec<VerifyRequest>,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct BatchVerifyResponse {
+    pub results: Vec<BatchVerifyResult>,
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+#[serde(untagged)]
+pub enum BatchVerifyResult {
+    Result(bool),
+    Error { error: String },
+}
+
+// Each candidate costs a full verification.
+const MAX_CANDIDATE_HASHES: usize = 10;
+
+#[derive(PartialEq, Eq)]
+pub enum Algorithm {
+    Argon2,
+    Bcrypt,
+    Scrypt,
+    Pbkdf2,
+    Balloon,
+}
+
+impl Algorithm {
+    fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Argon2 => "argon2",
+            Algorithm::Bcrypt => "bcrypt",
+            Algorithm::Scrypt => "scrypt",
+            Algorithm::Pbkdf2 => "pbkdf2",
+            Algorithm::Balloon => "balloon",
+        }
+    }
+}
+
+// ### Functions
+pub async fn verify_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let options: VerifyRequest = parse_body(&mut req, config.max_body_bytes).await?;
+
+    let password = decode_password(&options.password, options.password_encoding, options.pre_hashed)?;
+    let password = with_context(&password, options.context.as_deref());
+    let result = verify_any(&password, &options.hash, config, timing).await?;
+    let verify_response = VerifyResponse { result };
+    verify_response.to_json(config)
+}
+
+// For migrations that keep an old and a new hash side by side. Candidates are
+// tried in order up to the first match; one that can't be verified is
+// reported in `errors` instead of failing the others.
+pub async fn verify_multi_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let multi_req: MultiVerifyRequest = parse_body(&mut req, config.max_body_bytes).await?;
+    if multi_req.hashes.len() > MAX_CANDIDATE_HASHES {
+        return Err(Error::BatchTooLarge);
+    }
+
+    let password = decode_password(&multi_req.password, multi_req.password_encoding, multi_req.pre_hashed)?;
+    let password = with_context(&password, multi_req.context.as_deref());
+    let mut matched_index = None;
+    let mut errors = Vec::new();
+    for (index, hash) in multi_req.hashes.iter().enumerate() {
+        match verify_any(&password, hash, config, timing).await {
+            Ok(true) => {
+                matched_index = Some(index);
+                break;
+            }
+            Ok(false) => {}
+            Err(err) => errors.push(MultiVerifyError {
+                index,
+                error: err.message(),
+            }),
+        }
+    }
+
+    let multi_response = MultiVerifyResponse {
+        result: matched_index.is_some(),
+        matched_index,
+        errors,
+    };
+    serde_json::to_string(&multi_response).map_err(|_err| Error::InternalServerError)
+}
+
+// Every item is verified, whatever the others' outcome; one that can't be
+// verified gets an `error` in its place instead of failing the batch.
+pub async fn verify_batch_handler(mut req: Request, config: &Config, timing: &ServerTiming) -> Result<String, Error> {
+    let batch_req: BatchVerifyRequest = parse_body(&mut req, config.max_body_bytes).await?;
+    if batch_req.items.len() > MAX_BATCH_SIZE {
+        return Err(Error::BatchTooLarge);
+    }
+
+    let mut results = Vec::with_capacity(batch_req.items.len());
+    for item in &batch_req.items {
+        let result = match decode_password(&item.password, item.password_encoding, item.pre_hashed) {
+            Ok(password) => {
+                let password = with_context(&password, item.context.as_deref());
+                verify_any(&password, &item.hash, config, timing).await
+            }
+            Err(err) => Err(err),
+        };
+        results.push(result.map_or_else(|err| BatchVerifyResult::Error { error: err.message() }, BatchVerifyResult::Result));
+    }
+
+    let batch_response = BatchVerifyResponse { results };
+    serde_json::to_string(&batch_response).map_err(|_err| Error::InternalServerError)
+}
+
+// The `$pepper$v=<n>` prefix, if any, picks the pepper and is stripped before
+// detecting the algorithm.
+async fn verify_any(password: &[u8], hash: &str, config: &Config, timing: &ServerTiming) -> Result<bool, Error> {
+    let (pepper, hash) = config.peppers.for_hash(hash)?;
+    let password = pepper.apply(password);
+    let algorithm = detect_algorithm(hash)?;
+    if !config.algorithm_enabled(algorithm.name()) {
+        return Err(Error::UnsupportedHashFormat);
+    }
+    let result = timing.measure(|| match algorithm {
+        Algorithm::Argon2 => argon2id_verify(&password, hash, &config.argon2_limits, config.argon2_secret()),
+        Algorithm::Bcrypt => bcrypt_verify(&password, hash, config.bcrypt_max_work_factor),
+        Algorithm::Scrypt => scrypt_verify(&password, hash, &config.scrypt_limits),
+        Algorithm::Pbkdf2 => pbkdf2_verify(&password, hash, config.pbkdf2_max_rounds),
+        Algorithm::Balloon => balloon_verify(&password, hash, &config.balloon_limits),
+    })
+    .await?;
+    metrics::record_verify(algorithm.name(), result);
+    Ok(result)
+}
+
+// For the per-algorithm routes: a hash of another supported algorithm is sent
+// to the wrong endpoint rather than malformed.
+pub fn check_algorithm(hash: &str, expected: Algorithm) -> Result<(), Error> {
+    match detect_algorithm(hash) {
+        Ok(algorithm) if algorithm != expected => Err(Error::AlgorithmMismatch),
+        _ => Ok(()),
+    }
+}
+
+fn detect_algorithm(hash: &str) -> Result<Algorithm, Error> {
+    if hash.starts_with("$argon2") {
+        Ok(Algorithm::Argon2)
+    } else if ["$2a$", "$2b$", "$2y$"].iter().any(|prefix| hash.starts_with(prefix)) {
+        Ok(Algorithm::Bcrypt)
+    } else if hash.starts_with("$scrypt$") {
+        Ok(Algorithm::Scrypt)
+    } else if hash.starts_with("$pbkdf2") {
+        Ok(Algorithm::Pbkdf2)
+    } else if hash.starts_with("$balloon") {
+        Ok(Algorithm::Balloon)
+    } else {
+        Err(Error::UnsupportedHashFormat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Argon2Limits, Argon2Secret, BalloonLimits, ScryptLimits};
+
+    // Only the prefix is looked at before the mismatch is reported.
+    const HASHES: [(Algorithm, &str); 5] = [
+        (Algorithm::Argon2, "$argon2id$v=19$m=64,t=1,p=1$c2FsdHNhbHQ$aGFzaGhhc2hoYXNoaGFzaA"),
+        (Algorithm::Bcrypt, "$2b$04$abcdefghijklmnopqrstuuAd2NuvjYDzLoD2mOE9rZZ5ylGpx4Ywm"),
+        (Algorithm::Scrypt, "$scrypt$ln=4,r=8,p=1$c2FsdHNhbHQ$aGFzaGhhc2hoYXNoaGFzaA"),
+        (Algorithm::Pbkdf2, "$pbkdf2-sha256$i=1000$c2FsdHNhbHQ$aGFzaGhhc2hoYXNoaGFzaA"),
+        (Algorithm::Balloon, "$balloon$v=1$s=1024,t=3,p=1$c2FsdHNhbHQ$aGFzaGhhc2hoYXNoaGFzaA"),
+    ];
+
+    fn verify_as(algorithm: &Algorithm, hash: &str) -> Result<bool, Error> {
+        let argon2_limits = Argon2Limits {
+            max_memory_kib: 64 * 1024,
+            max_time_cost: 10,
+            max_parallelism: 8,
+            allow_custom_salt: false,
+        };
+        let scrypt_limits = ScryptLimits {
+            max_memory_kib: 64 * 1024,
+            max_p: 16,
+        };
+        let balloon_limits = BalloonLimits {
+            max_space_cost: 1 << 20,
+            max_time_cost: 16,
+        };
+        match algorithm {
+            Algorithm::Argon2 => argon2id_verify(b"hunter2", hash, &argon2_limits, &Argon2Secret::default()),
+            Algorithm::Bcrypt => bcrypt_verify(b"hunter2", hash, 14),
+            Algorithm::Scrypt => scrypt_verify(b"hunter2", hash, &scrypt_limits),
+            Algorithm::Pbkdf2 => pbkdf2_verify(b"hunter2", hash, 1_000_000),
+            Algorithm::Balloon => balloon_verify(b"hunter2", hash, &balloon_limits),
+        }
+    }
+
+    #[test]
+    fn hashes_sent_to_another_algorithm_are_a_mismatch() {
+        for (endpoint, _) in &HASHES {
+            for (algorithm, hash) in HASHES.iter().filter(|(algorithm, _)| algorithm != endpoint) {
+                let err = verify_as(endpoint, hash).err().unwrap();
+                assert_eq!(err.code(), "ALGORITHM_MISMATCH", "{} hash to {}", algorithm.name(), endpoint.name());
+            }
+        }
+    }
+
+    // Unknown formats are left for the algorithm's own parser to reject.
+    #[test]
+    fn matching_and_unknown_hashes_pass_the_check() {
+        for (algorithm, hash) in HASHES {
+            assert!(check_algorithm(hash, algorithm).is_ok());
+        }
+        assert!(check_algorithm("not-a-hash", Algorithm::Scrypt).is_ok());
+    }
+
+    // A 4-byte salt is valid PHC but too short for argon2 itself, so the hash
+    // parses and only fails once it is run: the server's fault, not the client's.
+    #[test]
+    fn hashes_that_fail_to_run_are_an_internal_failure() {
+        let hash = "$argon2id$v=19$m=64,t=1,p=1$c2FsdA$aGFzaGhhc2hoYXNoaGFzaA";
+        let err = verify_as(&Algorithm::Argon2, hash).err().unwrap();
+        assert_eq!(err.code(), "VERIFY_FAILED");
+        assert_eq!(err.status(), 500);
+    }
+}
@@ -0,0 +1,24 @@
+//! Build metadata for deployment tracking.
+
+use crate::error::Error;
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct VersionResponse {
+    pub version: &'static str,
+    pub git_sha: &'static str,
+    pub build_timestamp: &'static str,
+    pub argon2_crate_version: &'static str,
+    pub bcrypt_crate_version: &'static str,
+}
+
+// Everything is baked in by `build.rs`.
+pub fn version_handler() -> Result<String, Error> {
+    let version_response = VersionResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("GIT_SHA"),
+        build_timestamp: env!("BUILD_TIMESTAMP"),
+        argon2_crate_version: env!("ARGON2_CRATE_VERSION"),
+        bcrypt_crate_version: env!("BCRYPT_CRATE_VERSION"),
+    };
+    serde_json::to_string(&version_response).map_err(|_err| Error::InternalServerError)
+}